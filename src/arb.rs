@@ -0,0 +1,379 @@
+//! Find and trade crossed markets between the venues of a level.
+//!
+//! When one venue bids more than another asks, buying on the cheap venue
+//! and selling on the other locks in the difference. `ArbDetector` keeps a
+//! `ConsolidatedBook` and reports every such cross; `execute` sends the two
+//! legs as immediate-or-cancel orders and deals with a leg that misses.
+
+use backtest::MarketEvent;
+use client::LevelClient;
+use consolidated::{ConsolidatedBook, VenueLevel};
+use data::{Level, Order, OrderDirection, OrderResponse, OrderType};
+use error::{Error, Result};
+use http::HttpClient;
+use types::{Price, Qty, Symbol, Venue};
+
+/// A venue bidding above another venue's ask.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Opportunity {
+    pub symbol: Symbol,
+    pub buy_venue: Venue,
+    /// The ask to buy at.
+    pub buy_price: Price,
+    pub sell_venue: Venue,
+    /// The bid to sell at.
+    pub sell_price: Price,
+    /// Shares available on both sides at those prices.
+    pub qty: Qty,
+}
+
+impl Opportunity {
+    /// Cents made on each share.
+    pub fn edge(&self) -> u64 {
+        self.sell_price.0 - self.buy_price.0
+    }
+
+    /// Cents made if every share trades at the quoted prices.
+    pub fn expected_edge(&self) -> u64 {
+        self.edge() * self.qty.0
+    }
+}
+
+/// Every crossed pair of venues in a book, best expected edge first.
+///
+/// Only each venue's top of book is compared, so the size is what's shown
+/// at the best bid and ask and nothing deeper.
+pub fn crossed(book: &ConsolidatedBook) -> Vec<Opportunity> {
+    let asks = tops(book.asks());
+    let bids = tops(book.bids());
+    let mut found = Vec::new();
+    for ask in &asks {
+        for bid in &bids {
+            if bid.venue == ask.venue || bid.price <= ask.price {
+                continue;
+            }
+            found.push(Opportunity {
+                symbol: book.symbol().clone(),
+                buy_venue: ask.venue.clone(),
+                buy_price: ask.price,
+                sell_venue: bid.venue.clone(),
+                sell_price: bid.price,
+                qty: if ask.qty < bid.qty { ask.qty } else { bid.qty },
+            });
+        }
+    }
+    found.sort_by_key(|o| ::std::cmp::Reverse(o.expected_edge()));
+    found
+}
+
+/// Each venue's best level from a sorted ladder, with everything shown at
+/// that price added up.
+fn tops(ladder: Vec<VenueLevel>) -> Vec<VenueLevel> {
+    let mut tops: Vec<VenueLevel> = Vec::new();
+    for level in ladder {
+        match tops.iter_mut().find(|t| t.venue == level.venue) {
+            Some(t) => {
+                if t.price == level.price {
+                    t.qty += level.qty;
+                }
+            }
+            None => tops.push(level),
+        }
+    }
+    tops
+}
+
+/// Watches one symbol across a level's venues for crossed markets.
+#[derive(Debug, Clone)]
+pub struct ArbDetector {
+    book: ConsolidatedBook,
+    min_edge: u64,
+}
+
+impl ArbDetector {
+    pub fn new(level: &Level, symbol: Symbol) -> ArbDetector {
+        ArbDetector {
+            book: ConsolidatedBook::for_level(level, symbol),
+            min_edge: 1,
+        }
+    }
+
+    /// Ignore crosses making less than this many cents in total, e.g. to
+    /// cover the risk of a leg missing. Defaults to 1.
+    pub fn min_edge(mut self, cents: u64) -> ArbDetector {
+        self.min_edge = cents;
+        self
+    }
+
+    pub fn book(&self) -> &ConsolidatedBook {
+        &self.book
+    }
+
+    /// Apply a quote or order book and report any crosses, but only when
+    /// the best bid or offer moved.
+    pub fn update(&mut self, event: &MarketEvent) -> Vec<Opportunity> {
+        match self.book.update(event) {
+            Some(_) => self.opportunities(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Fetch every venue's order book and report any crosses.
+    pub fn poll<T: HttpClient + Clone>(&mut self,
+                                       client: &LevelClient<T>)
+                                       -> Result<Vec<Opportunity>> {
+        try!(self.book.poll(client));
+        Ok(self.opportunities())
+    }
+
+    /// Crosses in the book as it is now.
+    pub fn opportunities(&self) -> Vec<Opportunity> {
+        crossed(&self.book).into_iter().filter(|o| o.expected_edge() >= self.min_edge).collect()
+    }
+}
+
+/// What to do when one leg fills more than the other.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LegRisk {
+    /// Keep the difference as a position.
+    Hold,
+    /// Trade the difference away with a market order on the venue the
+    /// missed leg was meant for.
+    Unwind,
+}
+
+/// How a pair of arbitrage orders went.
+#[derive(Debug)]
+pub struct ArbOutcome {
+    pub buy: OrderResponse,
+    /// The sell leg, unless it couldn't be sent.
+    pub sell: Option<OrderResponse>,
+    /// The order that traded away a mismatch between the legs, if one
+    /// was needed.
+    pub unwind: Option<OrderResponse>,
+    /// Why the sell, or failing that the unwind, couldn't be sent.
+    pub error: Option<Error>,
+}
+
+impl ArbOutcome {
+    /// Shares bought minus shares sold, counting the unwind.
+    pub fn net_shares(&self) -> i64 {
+        self.orders()
+            .iter()
+            .map(|o| {
+                match o.direction {
+                    Some(OrderDirection::Buy) => o.total_filled.0 as i64,
+                    Some(OrderDirection::Sell) => -(o.total_filled.0 as i64),
+                    None => 0,
+                }
+            })
+            .sum()
+    }
+
+    /// Cents received minus cents paid across every fill.
+    pub fn cash(&self) -> i64 {
+        self.orders()
+            .iter()
+            .map(|o| {
+                let value: i64 = o.fills.iter().map(|f| (f.price.0 * f.qty.0) as i64).sum();
+                match o.direction {
+                    Some(OrderDirection::Buy) => -value,
+                    _ => value,
+                }
+            })
+            .sum()
+    }
+
+    fn orders(&self) -> Vec<&OrderResponse> {
+        let mut orders = vec![&self.buy];
+        orders.extend(self.sell.iter());
+        orders.extend(self.unwind.iter());
+        orders
+    }
+}
+
+/// Trade an opportunity: buy on one venue and sell on the other, both
+/// immediate-or-cancel at the quoted prices.
+///
+/// The buy goes first and the sell is sized to what it filled, so a buy
+/// that misses leaves nothing to unwind and returns `None`. A sell that
+/// misses, or can't be sent at all, leaves shares over, which `risk`
+/// decides what to do with.
+///
+/// # Errors
+///
+/// Errors out when the buy can't be sent. Once the buy has filled, errors
+/// sending the other orders end up in the outcome's `error` instead, so
+/// the shares bought aren't lost track of.
+pub fn execute<T: HttpClient + Clone>(client: &LevelClient<T>,
+                                      opportunity: &Opportunity,
+                                      risk: LegRisk)
+                                      -> Result<Option<ArbOutcome>> {
+    let o = opportunity;
+    let buy = try!(client.order(&try!(Order::builder(&client.level)
+        .venue(o.buy_venue.clone())
+        .stock(o.symbol.clone())
+        .buy()
+        .price(o.buy_price)
+        .qty(o.qty)
+        .order_type(OrderType::ImmediateOrCancel)
+        .build())));
+    if buy.total_filled.is_zero() {
+        return Ok(None);
+    }
+    let sell = Order::builder(&client.level)
+        .venue(o.sell_venue.clone())
+        .stock(o.symbol.clone())
+        .sell()
+        .price(o.sell_price)
+        .qty(buy.total_filled)
+        .order_type(OrderType::ImmediateOrCancel)
+        .build()
+        .map_err(Error::from)
+        .and_then(|order| client.order(&order));
+    let mut outcome = ArbOutcome {
+        buy: buy,
+        sell: None,
+        unwind: None,
+        error: None,
+    };
+    match sell {
+        Ok(sell) => outcome.sell = Some(sell),
+        Err(e) => {
+            warn!(stock = %o.symbol,
+                  venue = %o.sell_venue,
+                  error = %e,
+                  "arbitrage sell leg failed");
+            outcome.error = Some(e);
+        }
+    }
+    let missed = outcome.net_shares();
+    if missed > 0 {
+        warn!(stock = %o.symbol,
+              venue = %o.sell_venue,
+              missed = missed,
+              "arbitrage sell leg missed");
+        if risk == LegRisk::Unwind {
+            let unwind = Order::builder(&client.level)
+                .venue(o.sell_venue.clone())
+                .stock(o.symbol.clone())
+                .sell()
+                .qty(Qty(missed as u64))
+                .order_type(OrderType::Market)
+                .build()
+                .map_err(Error::from)
+                .and_then(|order| client.order(&order));
+            match unwind {
+                Ok(unwind) => outcome.unwind = Some(unwind),
+                Err(e) => {
+                    warn!(stock = %o.symbol, venue = %o.sell_venue, error = %e, "unwind failed");
+                    outcome.error = outcome.error.or(Some(e));
+                }
+            }
+        }
+    }
+    Ok(Some(outcome))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use client::LevelClient;
+    use consolidated::ConsolidatedBook;
+    use endpoint::ApiProfile;
+    use test_support::{TestExchange, book, level, test_level};
+    use types::{Price, Qty};
+
+    #[test]
+    fn test_crossed() {
+        let three = test_level(&["AEX", "BEX", "CEX"]);
+        let mut c = ConsolidatedBook::for_level(&three, "FOOBAR".into());
+        c.update_orderbook(&book("AEX", vec![level(4990, 10, true)], vec![level(5000, 10, false)]));
+        c.update_orderbook(&book("BEX", vec![level(5020, 4, true)], vec![level(5030, 10, false)]));
+        c.update_orderbook(&book("CEX", vec![level(5010, 20, true)], vec![level(5040, 10, false)]));
+        let found = crossed(&c);
+        assert_eq!(2, found.len());
+        assert_eq!(("AEX", "CEX"), (found[0].buy_venue.as_str(), found[0].sell_venue.as_str()));
+        assert_eq!(Qty(10), found[0].qty);
+        assert_eq!(100, found[0].expected_edge());
+        assert_eq!(("AEX", "BEX"), (found[1].buy_venue.as_str(), found[1].sell_venue.as_str()));
+        assert_eq!(20, found[1].edge());
+        assert_eq!(80, found[1].expected_edge());
+
+        let mut detector = ArbDetector::new(&three, "FOOBAR".into()).min_edge(90);
+        let quiet = MarketEvent::Orderbook(book("AEX", vec![], vec![level(5000, 10, false)]));
+        assert!(detector.update(&quiet).is_empty());
+        let cross = MarketEvent::Orderbook(book("CEX", vec![level(5010, 20, true)], vec![]));
+        assert_eq!(1, detector.update(&cross).len());
+    }
+
+    #[test]
+    fn test_execute_unwinds_missed_leg() {
+        let exchange = TestExchange::default();
+        exchange.fill_up_to.borrow_mut().insert("CEX".to_owned(), 6);
+        let client = LevelClient::new(exchange.clone(),
+                                      test_level(&["AEX", "BEX", "CEX"]),
+                                      "http://localhost:8000",
+                                      ApiProfile::default());
+        let opportunity = Opportunity {
+            symbol: "FOOBAR".into(),
+            buy_venue: "AEX".into(),
+            buy_price: Price(5000),
+            sell_venue: "CEX".into(),
+            sell_price: Price(5010),
+            qty: Qty(10),
+        };
+
+        let held = execute(&client, &opportunity, LegRisk::Hold).unwrap().unwrap();
+        assert_eq!(4, held.net_shares());
+        assert!(held.unwind.is_none());
+
+        exchange.fill_up_to.borrow_mut().insert("CEX".to_owned(), 100);
+        exchange.fill_up_to.borrow_mut().insert("AEX".to_owned(), 7);
+        let clean = execute(&client, &opportunity, LegRisk::Unwind).unwrap().unwrap();
+        assert_eq!(0, clean.net_shares());
+        assert_eq!(70, clean.cash());
+
+        exchange.fill_up_to.borrow_mut().insert("CEX".to_owned(), 6);
+        exchange.fill_up_to.borrow_mut().insert("AEX".to_owned(), 10);
+        let unwound = execute(&client, &opportunity, LegRisk::Unwind).unwrap().unwrap();
+        assert_eq!(0, unwound.net_shares());
+        let last = exchange.sent.borrow().last().cloned().unwrap();
+        assert!(matches!(last.order_type, OrderType::Market));
+        assert_eq!(Qty(4), last.qty);
+
+        exchange.fill_up_to.borrow_mut().insert("AEX".to_owned(), 0);
+        assert!(execute(&client, &opportunity, LegRisk::Unwind).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_execute_sell_leg_error() {
+        let exchange = TestExchange::default();
+        let client = LevelClient::new(exchange.clone(),
+                                      test_level(&["AEX", "CEX"]),
+                                      "http://localhost:8000",
+                                      ApiProfile::default());
+        let opportunity = Opportunity {
+            symbol: "FOOBAR".into(),
+            buy_venue: "AEX".into(),
+            buy_price: Price(5000),
+            sell_venue: "CEX".into(),
+            sell_price: Price(5010),
+            qty: Qty(10),
+        };
+
+        exchange.fail_next.borrow_mut().push("CEX".to_owned());
+        let held = execute(&client, &opportunity, LegRisk::Hold).unwrap().unwrap();
+        assert!(held.sell.is_none());
+        assert!(held.error.is_some());
+        assert_eq!(10, held.net_shares());
+        assert_eq!(-50000, held.cash());
+
+        exchange.fail_next.borrow_mut().push("CEX".to_owned());
+        let unwound = execute(&client, &opportunity, LegRisk::Unwind).unwrap().unwrap();
+        assert!(unwound.sell.is_none());
+        assert!(unwound.error.is_some());
+        assert_eq!(Qty(10), unwound.unwind.as_ref().unwrap().total_filled);
+        assert_eq!(0, unwound.net_shares());
+    }
+}
@@ -0,0 +1,723 @@
+use chrono::{DateTime, Duration, UTC};
+use data::{BidAsk, Fill, Order, OrderDirection, OrderResponse, OrderType, OrderbookResponse,
+           QuoteResponse, parse_response};
+use error::Result;
+use std::collections::{HashMap, VecDeque};
+use std::io::BufRead;
+use strategy::{Action, Actions, Strategy};
+use types::{OrderId, Price, Qty, Symbol, Venue};
+
+/// One piece of captured market data.
+#[derive(Debug, Clone)]
+pub enum MarketEvent {
+    Quote(QuoteResponse),
+    Orderbook(OrderbookResponse),
+}
+
+impl MarketEvent {
+    /// Parse a single captured response body. Either a quote or an order
+    /// book is accepted.
+    pub fn parse(buf: &str) -> Result<MarketEvent> {
+        match parse_response::<QuoteResponse>(buf) {
+            Ok(q) => Ok(MarketEvent::Quote(q)),
+            Err(_) => parse_response(buf).map(MarketEvent::Orderbook),
+        }
+    }
+
+    pub fn venue(&self) -> &Venue {
+        match *self {
+            MarketEvent::Quote(ref q) => &q.venue,
+            MarketEvent::Orderbook(ref b) => &b.venue,
+        }
+    }
+
+    pub fn symbol(&self) -> &Symbol {
+        match *self {
+            MarketEvent::Quote(ref q) => &q.symbol,
+            MarketEvent::Orderbook(ref b) => &b.symbol,
+        }
+    }
+
+    /// When the venue says this data was produced.
+    pub fn time(&self) -> Option<DateTime<UTC>> {
+        match *self {
+            MarketEvent::Quote(ref q) => q.quote_time,
+            MarketEvent::Orderbook(ref b) => Some(b.ts),
+        }
+    }
+}
+
+/// Read a capture that has one response body per line. Blank lines are skipped.
+pub fn read_events<R: BufRead>(reader: R) -> Result<Vec<MarketEvent>> {
+    let mut events = Vec::new();
+    for line in reader.lines() {
+        let line = try!(line);
+        if line.trim().is_empty() {
+            continue;
+        }
+        events.push(try!(MarketEvent::parse(&line)));
+    }
+    Ok(events)
+}
+
+/// Where a resting order joins the queue at its price level.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QueuePosition {
+    /// Ahead of everyone already resting at the price.
+    Front,
+    /// Behind everything already resting at the price. Orders only fill
+    /// after that much volume has traded at the price.
+    Back,
+}
+
+#[derive(Debug, Clone)]
+pub struct BacktestConfig {
+    /// Time between a strategy asking for an order or cancel and it
+    /// reaching the venue.
+    pub latency: Duration,
+    pub queue_position: QueuePosition,
+}
+
+impl Default for BacktestConfig {
+    fn default() -> BacktestConfig {
+        BacktestConfig {
+            latency: Duration::zero(),
+            queue_position: QueuePosition::Back,
+        }
+    }
+}
+
+/// A simulated execution of one of our orders.
+#[derive(Debug, Clone)]
+pub struct SimFill {
+    pub id: OrderId,
+    pub venue: Venue,
+    pub symbol: Symbol,
+    pub direction: OrderDirection,
+    pub price: Price,
+    pub qty: Qty,
+    pub ts: DateTime<UTC>,
+}
+
+/// Where we ended up in a single stock. Cash is in cents.
+#[derive(Debug, Clone)]
+pub struct Position {
+    pub venue: Venue,
+    pub symbol: Symbol,
+    pub shares: i64,
+    pub cash: i64,
+    /// Last trade price, or the mid if nothing traded.
+    pub mark: Option<Price>,
+}
+
+impl Position {
+    /// Cash plus the marked value of the shares held. Shares are valued at
+    /// zero when there was never a price to mark them at.
+    pub fn pnl(&self) -> i64 {
+        self.cash + self.mark.map_or(0, |m| self.shares * m.0 as i64)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BacktestReport {
+    pub fills: Vec<SimFill>,
+    pub orders_placed: u64,
+    pub orders_canceled: u64,
+    pub positions: Vec<Position>,
+    /// Orders and cancels still on their way to the venue, waiting out
+    /// the latency, when the events ran out.
+    pub unsent: Vec<Action>,
+}
+
+impl BacktestReport {
+    pub fn pnl(&self) -> i64 {
+        self.positions.iter().map(Position::pnl).sum()
+    }
+
+    pub fn filled_qty(&self) -> Qty {
+        self.fills.iter().map(|f| f.qty).sum()
+    }
+}
+
+/// The last recorded state of one stock on one venue.
+#[derive(Debug, Default)]
+struct Book {
+    bids: Vec<BidAsk>,
+    asks: Vec<BidAsk>,
+    last: Option<Price>,
+    last_trade: Option<DateTime<UTC>>,
+}
+
+impl Book {
+    fn crosses(is_buy: bool, level: Price, limit: Option<Price>) -> bool {
+        match limit {
+            None => true,
+            Some(l) if is_buy => level <= l,
+            Some(l) => level >= l,
+        }
+    }
+
+    /// How much could be taken by an order on the given side.
+    fn available(&self, is_buy: bool, limit: Option<Price>) -> Qty {
+        let levels = if is_buy { &self.asks } else { &self.bids };
+        levels.iter()
+            .take_while(|l| Book::crosses(is_buy, l.price, limit))
+            .map(|l| l.qty)
+            .sum()
+    }
+
+    /// Remove up to qty of liquidity from the other side of the book and
+    /// return the (price, qty) of each level hit.
+    fn take(&mut self, is_buy: bool, limit: Option<Price>, qty: Qty) -> Vec<(Price, Qty)> {
+        let levels = if is_buy { &mut self.asks } else { &mut self.bids };
+        let mut taken = Vec::new();
+        let mut remaining = qty;
+        for l in levels.iter_mut() {
+            if remaining.is_zero() || !Book::crosses(is_buy, l.price, limit) {
+                break;
+            }
+            let q = ::std::cmp::min(remaining, l.qty);
+            if !q.is_zero() {
+                l.qty -= q;
+                remaining -= q;
+                taken.push((l.price, q));
+            }
+        }
+        levels.retain(|l| !l.qty.is_zero());
+        taken
+    }
+
+    /// Quantity already resting on our side at a price.
+    fn resting_at(&self, is_buy: bool, price: Price) -> Qty {
+        let levels = if is_buy { &self.bids } else { &self.asks };
+        levels.iter().filter(|l| l.price == price).map(|l| l.qty).sum()
+    }
+
+    fn mark(&self) -> Option<Price> {
+        self.last.or_else(|| match (self.bids.first(), self.asks.first()) {
+            (Some(b), Some(a)) => Some(Price((b.price.0 + a.price.0) / 2)),
+            _ => None,
+        })
+    }
+}
+
+struct SimOrder {
+    response: OrderResponse,
+    queue_ahead: Qty,
+}
+
+#[derive(Default)]
+struct Ledger {
+    fills: Vec<SimFill>,
+    positions: HashMap<(Venue, Symbol), Position>,
+}
+
+impl Ledger {
+    fn record(&mut self, resp: &mut OrderResponse, price: Price, qty: Qty, at: DateTime<UTC>) {
+        resp.qty -= qty;
+        resp.total_filled += qty;
+        resp.fills.push(Fill {
+            price: price,
+            qty: qty,
+            ts: at,
+        });
+        let direction = resp.direction.clone().unwrap_or(OrderDirection::Buy);
+        let signed = match direction {
+            OrderDirection::Buy => qty.0 as i64,
+            OrderDirection::Sell => -(qty.0 as i64),
+        };
+        let key = (resp.venue.clone(), resp.symbol.clone());
+        let pos = self.positions.entry(key).or_insert_with(|| {
+            Position {
+                venue: resp.venue.clone(),
+                symbol: resp.symbol.clone(),
+                shares: 0,
+                cash: 0,
+                mark: None,
+            }
+        });
+        pos.shares += signed;
+        pos.cash -= signed * price.0 as i64;
+        self.fills.push(SimFill {
+            id: resp.id,
+            venue: resp.venue.clone(),
+            symbol: resp.symbol.clone(),
+            direction: direction,
+            price: price,
+            qty: qty,
+            ts: at,
+        });
+    }
+}
+
+fn is_buy(resp: &OrderResponse) -> bool {
+    !matches!(resp.direction, Some(OrderDirection::Sell))
+}
+
+/// Replays captured market data through a `Strategy` and simulates how
+/// its orders would have been filled against the recorded book.
+///
+/// Quotes replace the top of the book and carry the trades used to fill
+/// resting orders. Order books replace the full depth. Marketable orders
+/// take liquidity from whatever was last recorded, and that liquidity is
+/// gone until the next event for the stock refreshes it.
+pub struct Backtester<S: Strategy> {
+    config: BacktestConfig,
+    strategy: S,
+    now: Option<DateTime<UTC>>,
+    pending: VecDeque<(DateTime<UTC>, Action)>,
+    books: HashMap<(Venue, Symbol), Book>,
+    open: Vec<SimOrder>,
+    ledger: Ledger,
+    next_id: u64,
+    orders_placed: u64,
+    orders_canceled: u64,
+}
+
+impl<S: Strategy> Backtester<S> {
+    pub fn new(strategy: S, config: BacktestConfig) -> Backtester<S> {
+        Backtester {
+            config: config,
+            strategy: strategy,
+            now: None,
+            pending: VecDeque::new(),
+            books: HashMap::new(),
+            open: Vec::new(),
+            ledger: Ledger::default(),
+            next_id: 1,
+            orders_placed: 0,
+            orders_canceled: 0,
+        }
+    }
+
+    pub fn strategy(&self) -> &S {
+        &self.strategy
+    }
+
+    /// Feed every event through the simulation and report on the result.
+    pub fn run<I: IntoIterator<Item = MarketEvent>>(mut self, events: I) -> BacktestReport {
+        for e in events {
+            self.step(e);
+        }
+        self.report()
+    }
+
+    /// Advance the simulation by a single event.
+    ///
+    /// Events without a timestamp are treated as happening at the same
+    /// time as the one before them.
+    pub fn step(&mut self, event: MarketEvent) {
+        let t = match event.time().or(self.now) {
+            Some(t) => t,
+            None => UTC::now(),
+        };
+        self.now = Some(t);
+        self.deliver(t);
+
+        let key = (event.venue().clone(), event.symbol().clone());
+        let trade = self.apply(&key, &event);
+        self.match_resting(&key, trade, t);
+
+        let mut actions = Actions::new();
+        match event {
+            MarketEvent::Quote(ref q) => self.strategy.on_quote(q, &mut actions),
+            MarketEvent::Orderbook(ref b) => self.strategy.on_orderbook(b, &mut actions),
+        }
+        self.schedule(actions);
+    }
+
+    /// Summarize everything that has happened so far.
+    pub fn report(&self) -> BacktestReport {
+        let mut positions: Vec<Position> = self.ledger
+            .positions
+            .iter()
+            .map(|(k, p)| {
+                let mut p = p.clone();
+                p.mark = self.books.get(k).and_then(Book::mark);
+                p
+            })
+            .collect();
+        positions.sort_by(|a, b| (&a.venue, &a.symbol).cmp(&(&b.venue, &b.symbol)));
+        BacktestReport {
+            fills: self.ledger.fills.clone(),
+            orders_placed: self.orders_placed,
+            orders_canceled: self.orders_canceled,
+            positions: positions,
+            unsent: self.pending.iter().map(|(_, a)| a.clone()).collect(),
+        }
+    }
+
+    fn schedule(&mut self, mut actions: Actions) {
+        let at = self.now.unwrap_or_else(UTC::now) + self.config.latency;
+        for a in actions.drain() {
+            let idx = self.pending.iter().position(|&(t, _)| t > at).unwrap_or(self.pending.len());
+            self.pending.insert(idx, (at, a));
+        }
+    }
+
+    fn notify(&mut self, resp: &OrderResponse) {
+        let mut actions = Actions::new();
+        self.strategy.on_order_update(resp, &mut actions);
+        self.schedule(actions);
+    }
+
+    /// Carry out every action that has reached the venue by time t.
+    fn deliver(&mut self, t: DateTime<UTC>) {
+        while self.pending.front().is_some_and(|&(at, _)| at <= t) {
+            let (at, action) = self.pending.pop_front().unwrap();
+            match action {
+                Action::Place(o) => self.place(o, at),
+                Action::Cancel { venue, stock, id } => self.cancel(&venue, &stock, id),
+            }
+        }
+    }
+
+    fn place(&mut self, o: Order, at: DateTime<UTC>) {
+        self.orders_placed += 1;
+        let id = self.next_id;
+        self.next_id += 1;
+        let mut resp = OrderResponse {
+            ok: true,
+            symbol: o.stock.clone(),
+            venue: o.venue.clone(),
+            direction: Some(o.direction.clone()),
+            original_qty: o.qty,
+            qty: o.qty,
+            price: o.price,
+            order_type: o.order_type.clone(),
+            id: OrderId(id),
+            account: o.account.clone(),
+            ts: at,
+            fills: vec![],
+            total_filled: Qty(0),
+            open: true,
+        };
+        let buy = is_buy(&resp);
+        let limit = match o.order_type {
+            OrderType::Market => None,
+            _ => Some(o.price),
+        };
+
+        let book = self.books
+            .entry((o.venue.clone(), o.stock.clone()))
+            .or_default();
+        let killed = match o.order_type {
+            OrderType::FillOrKill => book.available(buy, limit) < o.qty,
+            _ => false,
+        };
+        if !killed {
+            for (price, qty) in book.take(buy, limit, o.qty) {
+                self.ledger.record(&mut resp, price, qty, at);
+            }
+        }
+
+        match o.order_type {
+            OrderType::Limit if !resp.qty.is_zero() => {
+                let queue_ahead = match self.config.queue_position {
+                    QueuePosition::Front => Qty(0),
+                    QueuePosition::Back => book.resting_at(buy, o.price),
+                };
+                self.open.push(SimOrder {
+                    response: resp.clone(),
+                    queue_ahead: queue_ahead,
+                });
+            }
+            _ => resp.open = false,
+        }
+        self.notify(&resp);
+    }
+
+    fn cancel(&mut self, venue: &str, stock: &str, id: OrderId) {
+        let idx = self.open.iter().position(|o| {
+            o.response.id == id && o.response.venue == venue && o.response.symbol == stock
+        });
+        if let Some(idx) = idx {
+            let mut o = self.open.remove(idx);
+            o.response.open = false;
+            self.orders_canceled += 1;
+            self.notify(&o.response);
+        }
+    }
+
+    /// Update the recorded book for a stock and return the trade, if any,
+    /// that the event is reporting for the first time.
+    fn apply(&mut self, key: &(Venue, Symbol), event: &MarketEvent) -> Option<(Price, Qty)> {
+        let book = self.books.entry(key.clone()).or_default();
+        match *event {
+            MarketEvent::Quote(ref q) => {
+                book.bids = q.bid
+                    .map(|p| {
+                        vec![BidAsk {
+                                 price: p,
+                                 qty: q.bid_size,
+                                 is_buy: true,
+                             }]
+                    })
+                    .unwrap_or_default();
+                book.asks = q.ask
+                    .map(|p| {
+                        vec![BidAsk {
+                                 price: p,
+                                 qty: q.ask_size,
+                                 is_buy: false,
+                             }]
+                    })
+                    .unwrap_or_default();
+                let new_trade = q.last_trade.is_some() && q.last_trade != book.last_trade;
+                book.last_trade = q.last_trade;
+                book.last = q.last.or(book.last);
+                if new_trade {
+                    q.last.map(|p| (p, q.last_size.unwrap_or_default()))
+                } else {
+                    None
+                }
+            }
+            MarketEvent::Orderbook(ref b) => {
+                book.bids = b.bids.clone().unwrap_or_default();
+                book.asks = b.asks.clone().unwrap_or_default();
+                None
+            }
+        }
+    }
+
+    /// Fill resting orders that the latest event shows were traded
+    /// through or crossed.
+    fn match_resting(&mut self,
+                     key: &(Venue, Symbol),
+                     trade: Option<(Price, Qty)>,
+                     at: DateTime<UTC>) {
+        let mut updated = Vec::new();
+        {
+            let book = self.books.entry(key.clone()).or_default();
+            for o in self.open.iter_mut() {
+                if o.response.venue != key.0 || o.response.symbol != key.1 {
+                    continue;
+                }
+                let buy = is_buy(&o.response);
+                let price = o.response.price;
+                let remaining = o.response.qty;
+                let mut fill = Qty(0);
+
+                // Cancels ahead of us move us up the queue.
+                o.queue_ahead = ::std::cmp::min(o.queue_ahead, book.resting_at(buy, price));
+
+                if let Some((last, size)) = trade {
+                    if (buy && last < price) || (!buy && last > price) {
+                        // Traded through us, but only as far as the size
+                        // that printed.
+                        fill = ::std::cmp::min(remaining, size);
+                    } else if last == price {
+                        if size > o.queue_ahead {
+                            fill = ::std::cmp::min(remaining, size - o.queue_ahead);
+                            o.queue_ahead = Qty(0);
+                        } else {
+                            o.queue_ahead -= size;
+                        }
+                    }
+                }
+                if fill < remaining {
+                    fill += book.take(buy, Some(price), remaining - fill)
+                        .iter()
+                        .map(|&(_, q)| q)
+                        .sum();
+                }
+                if !fill.is_zero() {
+                    self.ledger.record(&mut o.response, price, fill, at);
+                    o.response.open = !o.response.qty.is_zero();
+                    updated.push(o.response.clone());
+                }
+            }
+        }
+        self.open.retain(|o| o.response.open);
+        for resp in updated {
+            self.notify(&resp);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Duration, UTC};
+    use data::{BidAsk, Order, OrderDirection, OrderResponse, OrderType, OrderbookResponse,
+               QuoteResponse};
+    use strategy::{Actions, Strategy};
+    use types::{Price, Qty};
+
+    fn at(secs: i64) -> DateTime<UTC> {
+        let start: DateTime<UTC> = "2015-12-04T09:02:16Z".parse().unwrap();
+        start + Duration::seconds(secs)
+    }
+
+    fn quote(secs: i64, bid: u64, ask: u64, last: u64, last_size: u64) -> MarketEvent {
+        MarketEvent::Quote(QuoteResponse {
+            ok: true,
+            symbol: "BOOK".into(),
+            venue: "TESTEX".into(),
+            bid: Some(Price(bid)),
+            ask: Some(Price(ask)),
+            bid_size: Qty(100),
+            ask_size: Qty(100),
+            bid_depth: Qty(100),
+            ask_depth: Qty(100),
+            last: Some(Price(last)),
+            last_size: Some(Qty(last_size)),
+            last_trade: Some(at(secs)),
+            quote_time: Some(at(secs)),
+        })
+    }
+
+    fn book(secs: i64, asks: Vec<(u64, u64)>) -> MarketEvent {
+        MarketEvent::Orderbook(OrderbookResponse {
+            ok: true,
+            venue: "TESTEX".into(),
+            symbol: "BOOK".into(),
+            bids: None,
+            asks: Some(asks.into_iter()
+                .map(|(p, q)| {
+                    BidAsk {
+                        price: Price(p),
+                        qty: Qty(q),
+                        is_buy: false,
+                    }
+                })
+                .collect()),
+            ts: at(secs),
+        })
+    }
+
+    /// Sends a single order on the first event it sees.
+    struct OneShot {
+        order: Option<Order>,
+        updates: Vec<OrderResponse>,
+    }
+
+    impl OneShot {
+        fn new(price: u64, qty: u64, order_type: OrderType) -> OneShot {
+            OneShot {
+                order: Some(Order {
+                    account: "tstacc".into(),
+                    venue: "TESTEX".into(),
+                    stock: "BOOK".into(),
+                    price: Price(price),
+                    qty: Qty(qty),
+                    direction: OrderDirection::Buy,
+                    order_type: order_type,
+                }),
+                updates: vec![],
+            }
+        }
+    }
+
+    impl Strategy for OneShot {
+        fn on_quote(&mut self, _: &QuoteResponse, actions: &mut Actions) {
+            if let Some(o) = self.order.take() {
+                actions.place(o);
+            }
+        }
+        fn on_orderbook(&mut self, _: &OrderbookResponse, actions: &mut Actions) {
+            if let Some(o) = self.order.take() {
+                actions.place(o);
+            }
+        }
+        fn on_order_update(&mut self, order: &OrderResponse, _: &mut Actions) {
+            self.updates.push(order.clone());
+        }
+    }
+
+    #[test]
+    fn test_parse_events() {
+        let capture = "{\"ok\": true, \"symbol\": \"BOOK\", \"venue\": \"TESTEX\", \"bid\": \
+                       100, \"bidSize\": 5, \"askSize\": 0, \"bidDepth\": 5, \"askDepth\": 0, \
+                       \"quoteTime\": \"2015-12-04T09:02:16.680986205Z\"}\n\n{\"ok\": true, \
+                       \"venue\": \"TESTEX\", \"symbol\": \"BOOK\", \"bids\": null, \"asks\": \
+                       [{\"price\": 105, \"qty\": 10, \"isBuy\": false}], \"ts\": \
+                       \"2015-12-04T09:02:17.680986205Z\"}\n";
+        let events = read_events(capture.as_bytes()).unwrap();
+        assert_eq!(2, events.len());
+        match events[0] {
+            MarketEvent::Quote(ref q) => assert_eq!(Some(Price(100)), q.bid),
+            _ => panic!("expected a quote"),
+        }
+        assert!(events[1].time().is_some());
+    }
+
+    #[test]
+    fn test_latency_fills_against_later_book() {
+        let config = BacktestConfig {
+            latency: Duration::seconds(2),
+            queue_position: QueuePosition::Back,
+        };
+        let bt = Backtester::new(OneShot::new(0, 15, OrderType::Market), config);
+        let report = bt.run(vec![book(0, vec![(100, 50)]),
+                                 book(1, vec![(110, 10), (120, 10)]),
+                                 book(3, vec![(130, 100)])]);
+        assert_eq!(Qty(15), report.filled_qty());
+        assert_eq!(Price(110), report.fills[0].price);
+        assert_eq!(Price(120), report.fills[1].price);
+        assert_eq!(15, report.positions[0].shares);
+        assert_eq!(-(10 * 110 + 5 * 120), report.positions[0].cash);
+        assert!(report.unsent.is_empty());
+    }
+
+    #[test]
+    fn test_unsent_at_end_of_stream() {
+        let config = BacktestConfig {
+            latency: Duration::seconds(5),
+            queue_position: QueuePosition::Back,
+        };
+        let report = Backtester::new(OneShot::new(0, 15, OrderType::Market), config)
+            .run(vec![book(0, vec![(100, 50)]), book(1, vec![(100, 50)])]);
+        assert_eq!(0, report.orders_placed);
+        assert_eq!(1, report.unsent.len());
+        assert!(matches!(report.unsent[0], Action::Place(ref o) if o.qty == Qty(15)));
+    }
+
+    #[test]
+    fn test_trade_through_fills_printed_size() {
+        let front = BacktestConfig {
+            latency: Duration::zero(),
+            queue_position: QueuePosition::Front,
+        };
+        let report = Backtester::new(OneShot::new(100, 50, OrderType::Limit), front)
+            .run(vec![quote(0, 100, 105, 102, 1), quote(1, 99, 105, 99, 10)]);
+        assert_eq!(Qty(10), report.filled_qty());
+        assert_eq!(Price(100), report.fills[0].price);
+    }
+
+    #[test]
+    fn test_fill_or_kill_without_liquidity() {
+        let bt = Backtester::new(OneShot::new(100, 60, OrderType::FillOrKill),
+                                 BacktestConfig::default());
+        let report = bt.run(vec![book(0, vec![(100, 50)]), book(1, vec![(100, 50)])]);
+        assert_eq!(Qty(0), report.filled_qty());
+        assert_eq!(1, report.orders_placed);
+    }
+
+    #[test]
+    fn test_queue_position() {
+        let events = vec![quote(0, 100, 105, 102, 1),
+                          quote(1, 100, 105, 100, 60),
+                          quote(2, 100, 105, 100, 60)];
+
+        let front = BacktestConfig {
+            latency: Duration::zero(),
+            queue_position: QueuePosition::Front,
+        };
+        let report = Backtester::new(OneShot::new(100, 50, OrderType::Limit), front)
+            .run(events.clone());
+        assert_eq!(Qty(50), report.filled_qty());
+        assert_eq!(at(1), report.fills[0].ts);
+
+        // 100 shares were already bid at 100 so the first trade doesn't reach us.
+        let mut bt = Backtester::new(OneShot::new(100, 50, OrderType::Limit),
+                                     BacktestConfig::default());
+        for e in events {
+            bt.step(e);
+        }
+        let report = bt.report();
+        assert_eq!(Qty(20), report.filled_qty());
+        assert_eq!(at(2), report.fills[0].ts);
+        assert!(bt.strategy().updates.last().unwrap().open);
+    }
+}
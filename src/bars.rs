@@ -0,0 +1,264 @@
+//! Turn trades into OHLCV bars.
+//!
+//! Trades come from quotes (`last`, `last_size` and `last_trade`) or from
+//! execution feeds. A quote keeps showing the same last trade until there's
+//! a new one, so a trade is only counted when its `last_trade` time moves
+//! on.
+
+use chrono::{DateTime, UTC};
+use data::{Execution, QuoteResponse};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use types::{OrderId, Price, Qty, Symbol, Venue};
+
+/// When a bar closes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BarSpec {
+    /// Every fixed stretch of wall clock time, lined up on the epoch.
+    Time(Duration),
+    /// Every so many trading days, as passed to `set_trading_day`.
+    TradingDays(u64),
+    /// Every so many trades.
+    Ticks(usize),
+    /// Once at least this many shares have traded. A trade is never split
+    /// between bars, so a bar can go over.
+    Volume(Qty),
+}
+
+/// Open, high, low, close and volume for a stretch of trades.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Bar {
+    /// When the first trade in the bar happened.
+    pub start: DateTime<UTC>,
+    /// When the last trade in the bar happened.
+    pub end: DateTime<UTC>,
+    /// The trading day the bar started on, if days are being passed in.
+    pub trading_day: Option<u64>,
+    pub open: Price,
+    pub high: Price,
+    pub low: Price,
+    pub close: Price,
+    pub volume: Qty,
+    pub trades: usize,
+    /// Cents traded, for the vwap.
+    pub notional: u64,
+}
+
+impl Bar {
+    fn new(at: DateTime<UTC>, price: Price, qty: Qty, day: Option<u64>) -> Bar {
+        Bar {
+            start: at,
+            end: at,
+            trading_day: day,
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume: qty,
+            trades: 1,
+            notional: price.0 * qty.0,
+        }
+    }
+
+    fn add(&mut self, at: DateTime<UTC>, price: Price, qty: Qty) {
+        self.end = at;
+        self.high = self.high.max(price);
+        self.low = self.low.min(price);
+        self.close = price;
+        self.volume += qty;
+        self.trades += 1;
+        self.notional += price.0 * qty.0;
+    }
+
+    /// The volume weighted average price in cents, or the close if nothing
+    /// traded size.
+    pub fn vwap(&self) -> f64 {
+        if self.volume.is_zero() {
+            self.close.0 as f64
+        } else {
+            self.notional as f64 / self.volume.0 as f64
+        }
+    }
+}
+
+/// Builds bars one trade at a time.
+///
+/// Each update hands back the bar it closed, if any. Bars only hold trades,
+/// so a stretch of time with none doesn't make an empty bar.
+#[derive(Debug, Clone)]
+pub struct BarBuilder {
+    spec: BarSpec,
+    current: Option<Bar>,
+    /// Which time bucket or trading day the current bar belongs to.
+    bucket: Option<u64>,
+    day: Option<u64>,
+    last_trades: HashMap<(Venue, Symbol), DateTime<UTC>>,
+    executions: HashSet<(OrderId, OrderId, DateTime<UTC>)>,
+}
+
+impl BarBuilder {
+    pub fn new(spec: BarSpec) -> BarBuilder {
+        BarBuilder {
+            spec: spec,
+            current: None,
+            bucket: None,
+            day: None,
+            last_trades: HashMap::new(),
+            executions: HashSet::new(),
+        }
+    }
+
+    /// Add the last trade from a quote, unless it's been counted already.
+    pub fn on_quote(&mut self, q: &QuoteResponse) -> Option<Bar> {
+        let (price, qty, at) = match (q.last, q.last_size, q.last_trade) {
+            (Some(p), Some(n), Some(t)) => (p, n, t),
+            _ => return None,
+        };
+        let key = (q.venue.clone(), q.symbol.clone());
+        if self.last_trades.get(&key).is_some_and(|&seen| at <= seen) {
+            return None;
+        }
+        self.last_trades.insert(key, at);
+        self.on_trade(at, price, qty)
+    }
+
+    /// Add a trade from an execution feed. The other side of the same
+    /// trade, from another account's feed, isn't counted again.
+    pub fn on_execution(&mut self, e: &Execution) -> Option<Bar> {
+        if !self.executions.insert((e.standing_id, e.incoming_id, e.filled_at)) {
+            return None;
+        }
+        self.on_trade(e.filled_at, e.price, e.filled)
+    }
+
+    /// Add a trade.
+    pub fn on_trade(&mut self, at: DateTime<UTC>, price: Price, qty: Qty) -> Option<Bar> {
+        let closed = match self.spec {
+            BarSpec::Time(d) => {
+                let millis = d.as_secs() * 1000 + d.subsec_nanos() as u64 / 1000000;
+                let now = at.timestamp() as u64 * 1000 + at.timestamp_subsec_millis() as u64;
+                let bucket = now / millis.max(1);
+                self.roll(bucket)
+            }
+            _ => None,
+        };
+        match self.current {
+            Some(ref mut bar) => bar.add(at, price, qty),
+            None => self.current = Some(Bar::new(at, price, qty, self.day)),
+        }
+        let full = match (self.spec, self.current.as_ref()) {
+            (BarSpec::Ticks(n), Some(bar)) => bar.trades >= n,
+            (BarSpec::Volume(v), Some(bar)) => bar.volume >= v,
+            _ => false,
+        };
+        if full {
+            return self.current.take();
+        }
+        closed
+    }
+
+    /// Tell the builder which trading day it is. With `TradingDays` bars
+    /// this closes the current bar once enough days have gone by.
+    pub fn set_trading_day(&mut self, day: u64) -> Option<Bar> {
+        self.day = Some(day);
+        match self.spec {
+            BarSpec::TradingDays(n) => self.roll(day / n.max(1)),
+            _ => None,
+        }
+    }
+
+    /// The bar still being built.
+    pub fn current(&self) -> Option<&Bar> {
+        self.current.as_ref()
+    }
+
+    /// Close the bar being built, e.g. at the end of a session.
+    pub fn flush(&mut self) -> Option<Bar> {
+        self.current.take()
+    }
+
+    /// Move to a new bucket, closing the current bar if it belongs to an
+    /// older one.
+    fn roll(&mut self, bucket: u64) -> Option<Bar> {
+        let moved = self.bucket.is_some_and(|b| b != bucket);
+        self.bucket = Some(bucket);
+        if moved {
+            self.current.take()
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, UTC};
+    use data::QuoteResponse;
+    use std::time::Duration;
+    use types::{Price, Qty};
+
+    fn at(second: u32) -> DateTime<UTC> {
+        format!("2016-06-02T16:20:{:02}Z", second).parse().unwrap()
+    }
+
+    fn quote(price: u64, size: u64, second: u32) -> QuoteResponse {
+        QuoteResponse {
+            ok: true,
+            symbol: "FOOBAR".into(),
+            venue: "TESTEX".into(),
+            bid: None,
+            ask: None,
+            bid_size: Qty(0),
+            ask_size: Qty(0),
+            bid_depth: Qty(0),
+            ask_depth: Qty(0),
+            last: Some(Price(price)),
+            last_size: Some(Qty(size)),
+            last_trade: Some(at(second)),
+            quote_time: Some(at(second)),
+        }
+    }
+
+    #[test]
+    fn test_time_bars_skip_repeated_quotes() {
+        let mut b = BarBuilder::new(BarSpec::Time(Duration::from_secs(10)));
+        assert!(b.on_quote(&quote(5000, 10, 1)).is_none());
+        // The same trade on a newer quote.
+        assert!(b.on_quote(&quote(5000, 10, 1)).is_none());
+        assert!(b.on_quote(&quote(5200, 30, 4)).is_none());
+        assert!(b.on_quote(&quote(4900, 10, 8)).is_none());
+        let bar = b.on_quote(&quote(5100, 5, 12)).unwrap();
+        assert_eq!((Price(5000), Price(5200), Price(4900), Price(4900)),
+                   (bar.open, bar.high, bar.low, bar.close));
+        assert_eq!(Qty(50), bar.volume);
+        assert_eq!(3, bar.trades);
+        assert_eq!(5100.0, bar.vwap());
+        assert_eq!((at(1), at(8)), (bar.start, bar.end));
+        assert_eq!(Price(5100), b.flush().unwrap().open);
+    }
+
+    #[test]
+    fn test_tick_and_volume_bars() {
+        let mut ticks = BarBuilder::new(BarSpec::Ticks(2));
+        assert!(ticks.on_trade(at(0), Price(100), Qty(1)).is_none());
+        assert_eq!(2, ticks.on_trade(at(1), Price(101), Qty(1)).unwrap().trades);
+        assert!(ticks.current().is_none());
+
+        let mut volume = BarBuilder::new(BarSpec::Volume(Qty(100)));
+        assert!(volume.on_trade(at(0), Price(100), Qty(60)).is_none());
+        assert_eq!(Qty(120), volume.on_trade(at(1), Price(101), Qty(60)).unwrap().volume);
+    }
+
+    #[test]
+    fn test_trading_day_bars() {
+        let mut b = BarBuilder::new(BarSpec::TradingDays(2));
+        assert!(b.set_trading_day(4).is_none());
+        b.on_trade(at(0), Price(100), Qty(1));
+        assert!(b.set_trading_day(5).is_none());
+        b.on_trade(at(1), Price(102), Qty(1));
+        let bar = b.set_trading_day(6).unwrap();
+        assert_eq!(Some(4), bar.trading_day);
+        assert_eq!(2, bar.trades);
+    }
+}
@@ -0,0 +1,414 @@
+extern crate michromer;
+extern crate ratatui;
+
+use michromer::client::{Client, LevelClient};
+use michromer::config::{Config, Settings};
+use michromer::data::{Level, OrderDirection, OrderResponse, OrderType, OrderbookResponse,
+                      QuoteResponse};
+use michromer::http::AuthHttpClient;
+use michromer::types::{OrderId, Price, Qty, Symbol, Venue};
+use ratatui::Frame;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::widgets::{Block, Paragraph, Row, Table};
+use std::collections::VecDeque;
+use std::env;
+use std::io;
+use std::process;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+static USAGE: &'static str = "Usage: michromer-tui [--profile NAME] [--url URL] [--venue VENUE] \
+                              [--account ACCOUNT] <stock>
+
+Anything not passed as an option comes from the MICHROMER_* environment
+variables or ~/.config/michromer/config.toml.
+
+Keys:
+    b / s    join the best bid / ask with a limit order
+    B / S    take the best ask / bid with an immediate-or-cancel order
+    + / -    change the order size
+    c / C    cancel our newest / every open order
+    q        quit";
+
+static MAX_TRADES: usize = 200;
+static SIZE_STEP: Qty = Qty(100);
+
+/// Everything fetched from the venue in one polling pass.
+#[derive(Default)]
+struct Snapshot {
+    book: Option<OrderbookResponse>,
+    quote: Option<QuoteResponse>,
+    orders: Vec<OrderResponse>,
+    error: Option<String>,
+}
+
+struct App {
+    lc: LevelClient<AuthHttpClient>,
+    venue: Venue,
+    stock: Symbol,
+    size: Qty,
+    snapshot: Snapshot,
+    /// (time, price, qty) of trades seen on the tape, newest first.
+    trades: VecDeque<(String, Price, Qty)>,
+    last_trade: Option<String>,
+    status: String,
+}
+
+fn main() {
+    let mut settings = Settings::default();
+    let mut stock = None;
+    let mut iter = env::args().skip(1);
+    while let Some(a) = iter.next() {
+        match a.as_ref() {
+            "--profile" => settings.profile = iter.next(),
+            "--url" => settings.base_url = iter.next(),
+            "--venue" => settings.venue = iter.next(),
+            "--account" => settings.account = iter.next(),
+            _ if stock.is_none() && !a.starts_with('-') => stock = Some(a),
+            _ => fail(USAGE),
+        }
+    }
+    let stock = stock.unwrap_or_else(|| fail(USAGE));
+    let config = match Config::load_with(settings) {
+        Ok(c) => c,
+        Err(e) => fail(&e.to_string()),
+    };
+    let (account, venue) = match (config.account.clone(), config.venue.clone()) {
+        (Some(a), Some(v)) => (a, v),
+        _ => fail("Needs an account and venue, from the options or the config"),
+    };
+    let client = match Client::with_config(&config) {
+        Ok(c) => c,
+        Err(e) => fail(&e.to_string()),
+    };
+    let lc = client.level_client(Level { account: account.into(), ..Level::default() });
+
+    let (tx, rx) = mpsc::channel();
+    {
+        let (lc, venue, stock) = (lc.clone(), venue.clone(), stock.clone());
+        thread::spawn(move || poll(lc, venue, stock, tx));
+    }
+
+    let app = App {
+        lc: lc,
+        venue: venue.into(),
+        stock: stock.into(),
+        size: SIZE_STEP,
+        snapshot: Snapshot::default(),
+        trades: VecDeque::new(),
+        last_trade: None,
+        status: String::new(),
+    };
+    let mut terminal = ratatui::init();
+    let res = run(&mut terminal, app, rx);
+    ratatui::restore();
+    if let Err(e) = res {
+        fail(&e.to_string());
+    }
+}
+
+fn fail(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    process::exit(1);
+}
+
+/// Keep fetching the book, quote and our orders until the ui goes away.
+fn poll(lc: LevelClient<AuthHttpClient>, venue: String, stock: String, tx: Sender<Snapshot>) {
+    loop {
+        let mut s = Snapshot::default();
+        match lc.orderbook(&venue, &stock) {
+            Ok(b) => s.book = Some(b),
+            Err(e) => s.error = Some(e.to_string()),
+        }
+        match lc.quote(&venue, &stock) {
+            Ok(q) => s.quote = Some(q),
+            Err(e) => s.error = Some(e.to_string()),
+        }
+        match lc.stock_orders(&venue, &stock) {
+            Ok(o) => s.orders = o.orders,
+            Err(e) => s.error = Some(e.to_string()),
+        }
+        if tx.send(s).is_err() {
+            return;
+        }
+        thread::sleep(Duration::from_millis(250));
+    }
+}
+
+fn run(terminal: &mut ratatui::DefaultTerminal,
+       mut app: App,
+       rx: Receiver<Snapshot>)
+       -> io::Result<()> {
+    loop {
+        while let Ok(s) = rx.try_recv() {
+            app.update(s);
+        }
+        try!(terminal.draw(|f| draw(f, &app)));
+        if !try!(event::poll(Duration::from_millis(100))) {
+            continue;
+        }
+        if let Event::Key(k) = try!(event::read()) {
+            if k.kind != KeyEventKind::Press {
+                continue;
+            }
+            match k.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Char('b') => app.join(OrderDirection::Buy),
+                KeyCode::Char('s') => app.join(OrderDirection::Sell),
+                KeyCode::Char('B') => app.take(OrderDirection::Buy),
+                KeyCode::Char('S') => app.take(OrderDirection::Sell),
+                KeyCode::Char('+') | KeyCode::Char('=') => app.size += SIZE_STEP,
+                KeyCode::Char('-') if app.size > SIZE_STEP => app.size -= SIZE_STEP,
+                KeyCode::Char('c') => app.cancel_newest(),
+                KeyCode::Char('C') => app.cancel_all(),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl App {
+    fn update(&mut self, s: Snapshot) {
+        if let Some(ref q) = s.quote {
+            if let (Some(ts), Some(price)) = (q.last_trade, q.last) {
+                let ts = ts.format("%H:%M:%S%.3f").to_string();
+                // The same trade is reported by every quote until the next one.
+                if self.last_trade.as_ref() != Some(&ts) {
+                    self.trades.push_front((ts.clone(), price, q.last_size.unwrap_or_default()));
+                    self.trades.truncate(MAX_TRADES);
+                    self.last_trade = Some(ts);
+                }
+            }
+        }
+        if let Some(ref e) = s.error {
+            self.status = e.clone();
+        }
+        self.snapshot = s;
+    }
+
+    fn best(&self, direction: &OrderDirection) -> Option<Price> {
+        self.snapshot.quote.as_ref().and_then(|q| match *direction {
+            OrderDirection::Buy => q.bid,
+            OrderDirection::Sell => q.ask,
+        })
+    }
+
+    /// Rest a limit order at the best price on our own side.
+    fn join(&mut self, direction: OrderDirection) {
+        match self.best(&direction) {
+            Some(p) => self.send(direction, p, OrderType::Limit),
+            None => self.status = "Nothing to join".to_owned(),
+        }
+    }
+
+    /// Cross the spread for whatever is showing on the other side.
+    fn take(&mut self, direction: OrderDirection) {
+        let other = match direction {
+            OrderDirection::Buy => OrderDirection::Sell,
+            OrderDirection::Sell => OrderDirection::Buy,
+        };
+        match self.best(&other) {
+            Some(p) => self.send(direction, p, OrderType::ImmediateOrCancel),
+            None => self.status = "Nothing to take".to_owned(),
+        }
+    }
+
+    fn send(&mut self, direction: OrderDirection, price: Price, order_type: OrderType) {
+        let builder = match direction {
+            OrderDirection::Buy => self.lc.buy(),
+            OrderDirection::Sell => self.lc.sell(),
+        };
+        let o = match builder.venue(self.venue.clone())
+            .stock(self.stock.clone())
+            .price(price)
+            .qty(self.size)
+            .order_type(order_type)
+            .build() {
+            Ok(o) => o,
+            Err(e) => {
+                self.status = format!("Not sent: {}", e);
+                return;
+            }
+        };
+        self.status = match self.lc.order(&o) {
+            Ok(r) => {
+                format!("{} {} {} @ {} -> id {}, filled {}",
+                        o.order_type,
+                        o.direction,
+                        o.qty,
+                        o.price,
+                        r.id,
+                        r.total_filled)
+            }
+            Err(e) => e.to_string(),
+        };
+    }
+
+    fn open_orders(&self) -> Vec<&OrderResponse> {
+        self.snapshot.orders.iter().filter(|o| o.open).collect()
+    }
+
+    fn cancel(&self, id: OrderId) -> String {
+        match self.lc.delete_order(&self.venue, &self.stock, id) {
+            Ok(_) => format!("Canceled {}", id),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    fn cancel_newest(&mut self) {
+        let newest = self.open_orders().iter().map(|o| o.id).max();
+        self.status = match newest {
+            Some(id) => self.cancel(id),
+            None => "No open orders".to_owned(),
+        };
+    }
+
+    fn cancel_all(&mut self) {
+        let ids: Vec<OrderId> = self.open_orders().iter().map(|o| o.id).collect();
+        let results: Vec<String> = ids.into_iter().map(|id| self.cancel(id)).collect();
+        self.status = if results.is_empty() {
+            "No open orders".to_owned()
+        } else {
+            results.join(", ")
+        };
+    }
+
+    /// Shares held and cash spent, from the fills on every order we know of.
+    fn position(&self) -> (i64, i64) {
+        let mut shares = 0i64;
+        let mut cash = 0i64;
+        for o in &self.snapshot.orders {
+            let sign = match o.direction {
+                Some(OrderDirection::Sell) => -1,
+                _ => 1,
+            };
+            for f in &o.fills {
+                shares += sign * f.qty.0 as i64;
+                cash -= sign * f.qty.0 as i64 * f.price.0 as i64;
+            }
+        }
+        (shares, cash)
+    }
+
+    /// Quantity our open orders have resting at a price on one side.
+    fn ours_at(&self, price: Price, is_buy: bool) -> Qty {
+        self.open_orders()
+            .iter()
+            .filter(|o| {
+                let buy = !matches!(o.direction, Some(OrderDirection::Sell));
+                o.price == price && buy == is_buy
+            })
+            .map(|o| o.qty)
+            .sum()
+    }
+}
+
+fn signed_dollars(cents: i64) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+    format!("{}{}", sign, Price(cents.unsigned_abs()))
+}
+
+fn draw(frame: &mut Frame, app: &App) {
+    let [title, body, status] = Layout::vertical([Constraint::Length(1),
+                                                  Constraint::Min(0),
+                                                  Constraint::Length(1)])
+        .areas(frame.area());
+    let [ladder, right] = Layout::horizontal([Constraint::Percentage(50),
+                                              Constraint::Percentage(50)])
+        .areas(body);
+    let [trades, orders, position] = Layout::vertical([Constraint::Percentage(45),
+                                                       Constraint::Percentage(40),
+                                                       Constraint::Min(4)])
+        .areas(right);
+
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    frame.render_widget(Paragraph::new(format!("{} {} on {}   size {}   q quit  b/s join  \
+                                                B/S take  +/- size  c/C cancel",
+                                               app.lc.level.account,
+                                               app.stock,
+                                               app.venue,
+                                               app.size))
+                            .style(bold),
+                        title);
+    frame.render_widget(Paragraph::new(app.status.clone()), status);
+
+    // Asks on top with the highest price first, then bids below them.
+    let mut rows = Vec::new();
+    if let Some(ref book) = app.snapshot.book {
+        if let Some(ref asks) = book.asks {
+            for a in asks.iter().rev() {
+                rows.push(Row::new(vec![String::new(),
+                                        a.price.to_string(),
+                                        a.qty.to_string(),
+                                        qty_or_blank(app.ours_at(a.price, false))])
+                    .style(Style::default().fg(Color::Red)));
+            }
+        }
+        if let Some(ref bids) = book.bids {
+            for b in bids {
+                rows.push(Row::new(vec![b.qty.to_string(),
+                                        b.price.to_string(),
+                                        String::new(),
+                                        qty_or_blank(app.ours_at(b.price, true))])
+                    .style(Style::default().fg(Color::Green)));
+            }
+        }
+    }
+    frame.render_widget(Table::new(rows, [Constraint::Length(10); 4])
+                            .header(Row::new(vec!["Bid Qty", "Price", "Ask Qty", "Ours"])
+                                .style(bold))
+                            .block(Block::bordered().title("Book")),
+                        ladder);
+
+    let trade_rows: Vec<Row> = app.trades
+        .iter()
+        .map(|&(ref ts, price, qty)| Row::new(vec![ts.clone(), price.to_string(), qty.to_string()]))
+        .collect();
+    frame.render_widget(Table::new(trade_rows,
+                                   [Constraint::Length(14),
+                                    Constraint::Length(10),
+                                    Constraint::Length(8)])
+                            .header(Row::new(vec!["Time", "Price", "Qty"]).style(bold))
+                            .block(Block::bordered().title("Trades")),
+                        trades);
+
+    let order_rows: Vec<Row> = app.open_orders()
+        .iter()
+        .map(|o| {
+            Row::new(vec![o.id.to_string(),
+                          o.direction.as_ref().map_or(String::new(), |d| d.to_string()),
+                          o.price.to_string(),
+                          format!("{}/{}", o.total_filled, o.original_qty)])
+        })
+        .collect();
+    frame.render_widget(Table::new(order_rows,
+                                   [Constraint::Length(10),
+                                    Constraint::Length(5),
+                                    Constraint::Length(10),
+                                    Constraint::Length(12)])
+                            .header(Row::new(vec!["Id", "Side", "Price", "Filled"]).style(bold))
+                            .block(Block::bordered().title("Open Orders")),
+                        orders);
+
+    let (shares, cash) = app.position();
+    let last = app.snapshot.quote.as_ref().and_then(|q| q.last);
+    let nav = last.map_or(String::from("-"),
+                          |l| signed_dollars(cash + shares * l.0 as i64));
+    frame.render_widget(Paragraph::new(format!("Shares {}\nCash   {}\nNAV    {}",
+                                               shares,
+                                               signed_dollars(cash),
+                                               nav))
+                            .block(Block::bordered().title("Position")),
+                        position);
+}
+
+fn qty_or_blank(qty: Qty) -> String {
+    if qty.is_zero() {
+        String::new()
+    } else {
+        qty.to_string()
+    }
+}
@@ -0,0 +1,401 @@
+extern crate michromer;
+extern crate serde;
+extern crate serde_json;
+
+use michromer::client::Client;
+use michromer::config::{Config, Settings};
+use michromer::data::{Level, Order, OrderDirection, OrderResponse, OrderType};
+use michromer::http::AuthHttpClient;
+use michromer::levels::{self, SolveConfig};
+use serde::Serialize;
+use std::env;
+use std::process;
+use std::slice;
+use std::str::FromStr;
+use std::time::Duration;
+
+static USAGE: &'static str = "Usage: michromer [options] <command>
+
+Options:
+    --json               print the raw response
+    --profile NAME       config profile to use, live or sim by default
+    --url URL            server to talk to
+    --venue VENUE        venue for commands that need one
+    --account ACCOUNT    account for commands that need one
+    --timeout MS         read and write timeout
+
+Commands:
+    level start <level>
+    level status <instance>
+    level stop <instance>
+    level restart <instance>
+    solve <level>        run a reference solver: first_steps, chock_a_block,
+                         sell_side, dueling_bulldozers or irrational_exuberance
+    heartbeat [venue]
+    stocks
+    quote <stock>
+    book <stock>
+    order buy|sell <stock> <qty> [price, e.g. $51.23] [--type limit|market|fok|ioc]
+    cancel <stock> <id>
+    orders [stock]
+
+Anything not passed as an option comes from the MICHROMER_* environment
+variables or ~/.config/michromer/config.toml.";
+
+struct Options {
+    json: bool,
+    order_type: OrderType,
+    settings: Settings,
+    args: Vec<String>,
+}
+
+fn main() {
+    let opts = match parse_options(env::args().skip(1).collect()) {
+        Ok(o) => o,
+        Err(e) => fail(&e),
+    };
+    let config = match Config::load_with(opts.settings.clone()) {
+        Ok(c) => c,
+        Err(e) => fail(&e.to_string()),
+    };
+    let client = match Client::with_config(&config) {
+        Ok(c) => c,
+        Err(e) => fail(&e.to_string()),
+    };
+    if let Err(e) = run(&client, &config, &opts) {
+        fail(&e);
+    }
+}
+
+fn fail(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    process::exit(1);
+}
+
+fn parse_options(raw: Vec<String>) -> Result<Options, String> {
+    let mut opts = Options {
+        json: false,
+        order_type: OrderType::Limit,
+        settings: Settings::default(),
+        args: vec![],
+    };
+    let mut iter = raw.into_iter();
+    while let Some(a) = iter.next() {
+        match a.as_ref() {
+            "--json" => opts.json = true,
+            "--profile" => opts.settings.profile = Some(try!(value(&a, iter.next()))),
+            "--url" => opts.settings.base_url = Some(try!(value(&a, iter.next()))),
+            "--venue" => opts.settings.venue = Some(try!(value(&a, iter.next()))),
+            "--account" => opts.settings.account = Some(try!(value(&a, iter.next()))),
+            "--timeout" => {
+                let ms = try!(value(&a, iter.next()));
+                let ms = try!(ms.parse().map_err(|_| "--timeout must be a number".to_owned()));
+                opts.settings.read_timeout = Some(Duration::from_millis(ms));
+                opts.settings.write_timeout = Some(Duration::from_millis(ms));
+            }
+            "--type" => {
+                let t = try!(iter.next().ok_or("--type needs a value"));
+                opts.order_type = try!(parse_order_type(&t));
+            }
+            "-h" | "--help" => return Err(USAGE.to_owned()),
+            _ => opts.args.push(a),
+        }
+    }
+    if opts.args.is_empty() {
+        return Err(USAGE.to_owned());
+    }
+    Ok(opts)
+}
+
+fn value(flag: &str, v: Option<String>) -> Result<String, String> {
+    v.ok_or_else(|| format!("{} needs a value", flag))
+}
+
+fn parse_order_type(t: &str) -> Result<OrderType, String> {
+    match t {
+        "limit" => Ok(OrderType::Limit),
+        "market" => Ok(OrderType::Market),
+        "fok" | "fill-or-kill" => Ok(OrderType::FillOrKill),
+        "ioc" | "immediate-or-cancel" => Ok(OrderType::ImmediateOrCancel),
+        _ => Err(format!("Unknown order type {}", t)),
+    }
+}
+
+fn arg<'a>(args: &'a [String], idx: usize, name: &str) -> Result<&'a str, String> {
+    args.get(idx).map(|s| s.as_ref()).ok_or_else(|| format!("Missing <{}>\n\n{}", name, USAGE))
+}
+
+fn parse_arg<N: FromStr>(args: &[String], idx: usize, name: &str) -> Result<N, String> {
+    let a = try!(arg(args, idx, name));
+    a.parse().map_err(|_| format!("<{}> can't be {}", name, a))
+}
+
+fn config_venue(config: &Config) -> Result<&str, String> {
+    config.venue
+        .as_ref()
+        .map(|v| v.as_ref())
+        .ok_or_else(|| "No venue, pass --venue or set one in the config".to_owned())
+}
+
+fn config_account(config: &Config) -> Result<&str, String> {
+    config.account
+        .as_ref()
+        .map(|a| a.as_ref())
+        .ok_or_else(|| "No account, pass --account or set one in the config".to_owned())
+}
+
+fn run(client: &Client<AuthHttpClient>, config: &Config, opts: &Options) -> Result<(), String> {
+    let args = &opts.args;
+    // A level client for calls that only need an account, not a started level.
+    let lc = client.level_client(Level {
+        account: config.account.clone().unwrap_or_default().into(),
+        ..Level::default()
+    });
+    match try!(arg(args, 0, "command")) {
+        "level" => {
+            match try!(arg(args, 1, "start|status|stop|restart")) {
+                "start" => {
+                    let name = try!(arg(args, 2, "level"));
+                    let lc = try!(client.start_level(name).map_err(|e| e.to_string()));
+                    print_level(opts, &lc.level)
+                }
+                "restart" => {
+                    let id = try!(parse_arg(args, 2, "instance"));
+                    let lc = try!(client.restart_level(id).map_err(|e| e.to_string()));
+                    print_level(opts, &lc.level)
+                }
+                "status" => {
+                    let id = try!(parse_arg(args, 2, "instance"));
+                    let s = try!(client.instance_status(id).map_err(|e| e.to_string()));
+                    output(opts, &s, || {
+                        let mut rows = vec![kv("instance", s.id),
+                                            kv("state", &s.state),
+                                            kv("done", s.done)];
+                        if let Some(ref d) = s.details {
+                            rows.push(kv("trading day",
+                                         format!("{}/{}", d.trading_day, d.end_of_the_world_day)));
+                        }
+                        if let Some(ref flash) = s.flash {
+                            for (k, v) in flash {
+                                rows.push(kv(k, v));
+                            }
+                        }
+                        table(&["", ""], rows)
+                    })
+                }
+                "stop" => {
+                    let id = try!(parse_arg(args, 2, "instance"));
+                    let s = try!(client.stop_level(id).map_err(|e| e.to_string()));
+                    output(opts, &s, || {
+                        match s.error {
+                            Some(ref e) if !e.is_empty() => format!("error: {}", e),
+                            _ => "stopped".to_owned(),
+                        }
+                    })
+                }
+                other => Err(format!("Unknown level command {}\n\n{}", other, USAGE)),
+            }
+        }
+        "solve" => {
+            let name = try!(arg(args, 1, "level"));
+            let o = try!(levels::solve(client, name, &SolveConfig::default())
+                .map_err(|e| e.to_string()));
+            output(opts, &o, || {
+                let mut rows = vec![kv("instance", o.instance_id),
+                                    kv("done", o.done),
+                                    kv("bought", o.bought),
+                                    kv("sold", o.sold),
+                                    kv("position", o.position()),
+                                    kv("cash", o.cash)];
+                for h in &o.hints {
+                    rows.push(kv("hint", format!("{:?}", h)));
+                }
+                table(&["", ""], rows)
+            })
+        }
+        "heartbeat" => {
+            match args.get(1) {
+                Some(venue) => {
+                    let h = try!(lc.venue_heart_beat(venue).map_err(|e| e.to_string()));
+                    output(opts,
+                           &h,
+                           || format!("{}: {}", h.venue, if h.ok { "up" } else { "down" }))
+                }
+                None => {
+                    let h = try!(lc.heart_beat().map_err(|e| e.to_string()));
+                    output(opts, &h, || {
+                        if h.ok {
+                            "up".to_owned()
+                        } else {
+                            format!("down: {}", h.error)
+                        }
+                    })
+                }
+            }
+        }
+        "stocks" => {
+            let venue = try!(config_venue(config));
+            let s = try!(lc.stock_list(venue).map_err(|e| e.to_string()));
+            output(opts, &s, || {
+                table(&["Symbol", "Name"],
+                      s.symbols.iter().map(|s| vec![s.symbol.to_string(), s.name.clone()]).collect())
+            })
+        }
+        "quote" => {
+            let venue = try!(config_venue(config));
+            let stock = try!(arg(args, 1, "stock"));
+            let q = try!(lc.quote(venue, stock).map_err(|e| e.to_string()));
+            output(opts, &q, || {
+                table(&["Venue", "Symbol", "Bid", "Bid Size", "Ask", "Ask Size", "Last",
+                        "Last Size", "Quote Time"],
+                      vec![vec![q.venue.to_string(),
+                                q.symbol.to_string(),
+                                opt(q.bid),
+                                q.bid_size.to_string(),
+                                opt(q.ask),
+                                q.ask_size.to_string(),
+                                opt(q.last),
+                                opt(q.last_size),
+                                opt(q.quote_time.map(|t| t.to_rfc3339()))]])
+            })
+        }
+        "book" => {
+            let venue = try!(config_venue(config));
+            let stock = try!(arg(args, 1, "stock"));
+            let b = try!(lc.orderbook(venue, stock).map_err(|e| e.to_string()));
+            output(opts, &b, || {
+                let mut rows = Vec::new();
+                if let Some(ref asks) = b.asks {
+                    for a in asks.iter().rev() {
+                        rows.push(vec!["ask".to_owned(), a.price.to_string(), a.qty.to_string()]);
+                    }
+                }
+                if let Some(ref bids) = b.bids {
+                    for bid in bids {
+                        rows.push(vec!["bid".to_owned(), bid.price.to_string(), bid.qty.to_string()]);
+                    }
+                }
+                table(&["Side", "Price", "Qty"], rows)
+            })
+        }
+        "order" => {
+            let direction = match try!(arg(args, 1, "buy|sell")) {
+                "buy" => OrderDirection::Buy,
+                "sell" => OrderDirection::Sell,
+                other => return Err(format!("Unknown direction {}", other)),
+            };
+            try!(config_account(config));
+            let mut b = Order::builder(&lc.level)
+                .venue(try!(config_venue(config)))
+                .stock(try!(arg(args, 2, "stock")))
+                .qty(try!(parse_arg(args, 3, "qty")))
+                .direction(direction)
+                .order_type(opts.order_type.clone());
+            if args.len() > 4 {
+                b = b.price(try!(parse_arg(args, 4, "price")));
+            }
+            let o = try!(b.build().map_err(|e| e.to_string()));
+            let r = try!(lc.order(&o).map_err(|e| e.to_string()));
+            output(opts, &r, || order_table(slice::from_ref(&r)))
+        }
+        "cancel" => {
+            let venue = try!(config_venue(config));
+            let stock = try!(arg(args, 1, "stock"));
+            let id = try!(parse_arg(args, 2, "id"));
+            let r = try!(lc.delete_order(venue, stock, id).map_err(|e| e.to_string()));
+            output(opts, &r, || order_table(slice::from_ref(&r)))
+        }
+        "orders" => {
+            try!(config_account(config));
+            let venue = try!(config_venue(config));
+            let r = try!(match args.get(1) {
+                    Some(stock) => lc.stock_orders(venue, stock),
+                    None => lc.orders(venue),
+                }
+                .map_err(|e| e.to_string()));
+            output(opts, &r, || order_table(&r.orders))
+        }
+        other => Err(format!("Unknown command {}\n\n{}", other, USAGE)),
+    }
+}
+
+fn print_level(opts: &Options, level: &Level) -> Result<(), String> {
+    output(opts, level, || {
+        let mut out = table(&["", ""],
+                            vec![kv("instance", level.instance_id),
+                                 kv("account", &level.account),
+                                 kv("venues", level.venues.join(", ")),
+                                 kv("tickers", level.tickers.join(", "))]);
+        for (title, body) in &level.instructions {
+            out = out + "\n## " + title + "\n\n" + body + "\n";
+        }
+        out
+    })
+}
+
+/// Print either the raw response as json or the human readable rendering.
+fn output<S: Serialize, F: FnOnce() -> String>(opts: &Options,
+                                               value: &S,
+                                               pretty: F)
+                                               -> Result<(), String> {
+    if opts.json {
+        let s = try!(serde_json::to_string_pretty(value).map_err(|e| e.to_string()));
+        println!("{}", s);
+    } else {
+        println!("{}", pretty());
+    }
+    Ok(())
+}
+
+fn order_table(orders: &[OrderResponse]) -> String {
+    table(&["Id", "Venue", "Symbol", "Side", "Type", "Price", "Filled", "Open"],
+          orders.iter()
+              .map(|o| {
+                  vec![o.id.to_string(),
+                       o.venue.to_string(),
+                       o.symbol.to_string(),
+                       o.direction.as_ref().map_or(String::new(), |d| d.to_string()),
+                       o.order_type.to_string(),
+                       o.price.to_string(),
+                       format!("{}/{}", o.total_filled, o.original_qty),
+                       o.open.to_string()]
+              })
+              .collect())
+}
+
+fn kv<V: ToString>(k: &str, v: V) -> Vec<String> {
+    vec![k.to_owned(), v.to_string()]
+}
+
+fn opt<V: ToString>(v: Option<V>) -> String {
+    v.map_or(String::new(), |v| v.to_string())
+}
+
+/// Lay rows out in left aligned columns. An all empty header is left off.
+fn table(headers: &[&str], rows: Vec<Vec<String>>) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.len()).collect();
+    for r in &rows {
+        for (i, c) in r.iter().enumerate() {
+            widths[i] = ::std::cmp::max(widths[i], c.len());
+        }
+    }
+    let line = |cells: Vec<&str>| -> String {
+        cells.iter()
+            .zip(widths.iter())
+            .map(|(c, w)| format!("{:1$}", c, w))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_owned()
+    };
+    let mut out = Vec::new();
+    if headers.iter().any(|h| !h.is_empty()) {
+        let dashes: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+        out.push(line(headers.to_vec()));
+        out.push(line(dashes.iter().map(|d| d.as_ref()).collect()));
+    }
+    for r in &rows {
+        out.push(line(r.iter().map(|c| c.as_ref()).collect()));
+    }
+    out.join("\n")
+}
@@ -0,0 +1,262 @@
+//! Pull the numbers a bot needs out of a level's instructions.
+//!
+//! `start_level` hands back `Level.instructions`, markdown meant for people
+//! ("Buy 100,000 shares ... don't let your position go over 1,000 shares").
+//! `LevelBrief` reads the target quantity, direction and position limit out
+//! of it, and keeps the markdown split into sections for anything else.
+
+use data::{Level, OrderDirection};
+use types::Qty;
+
+/// One titled piece of a level's instructions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    pub title: String,
+    pub body: String,
+}
+
+/// What a level wants done, as far as its instructions say.
+///
+/// Fields the text doesn't mention are left empty; `for_level` fills them
+/// in for the levels we know.
+#[derive(Debug, Clone, Default)]
+pub struct LevelBrief {
+    /// Whether the level wants shares bought or sold.
+    pub direction: Option<OrderDirection>,
+    /// How many shares to buy or sell.
+    pub target_qty: Option<Qty>,
+    /// The most shares to hold, long or short.
+    pub position_limit: Option<Qty>,
+    /// Net asset value or profit to reach, in cents.
+    pub target_nav: Option<i64>,
+    /// The level wants us to make a market rather than fill an order.
+    pub market_making: bool,
+    /// Every section of the instructions, "Instructions" first and the
+    /// rest by title, split further at markdown headings.
+    pub sections: Vec<Section>,
+}
+
+impl LevelBrief {
+    /// Read whatever can be found in any level's instructions.
+    pub fn parse(level: &Level) -> LevelBrief {
+        let mut titles: Vec<&String> = level.instructions.keys().collect();
+        titles.sort_by_key(|t| (t.as_str() != "Instructions", t.as_str()));
+        let mut brief = LevelBrief::default();
+        for title in titles {
+            let body = &level.instructions[title];
+            brief.sections.extend(sections(title, body));
+            for sentence in sentences(body) {
+                brief.read(&sentence);
+            }
+        }
+        brief
+    }
+
+    /// Read a level's instructions, then fill in anything they didn't say
+    /// from what we know about the level called `name`.
+    pub fn for_level(name: &str, level: &Level) -> LevelBrief {
+        let mut brief = LevelBrief::parse(level);
+        let (direction, qty, limit, market_making) = match name {
+            "first_steps" => (Some(OrderDirection::Buy), Some(Qty(100)), None, false),
+            "chock_a_block" => (Some(OrderDirection::Buy), Some(Qty(100000)), None, false),
+            "sell_side" | "dueling_bulldozers" => (None, None, Some(Qty(1000)), true),
+            "irrational_exuberance" => (None, None, Some(Qty(1000)), false),
+            _ => (None, None, None, false),
+        };
+        if brief.direction.is_none() {
+            brief.direction = direction;
+        }
+        if brief.target_qty.is_none() {
+            brief.target_qty = qty;
+        }
+        if brief.position_limit.is_none() {
+            brief.position_limit = limit;
+        }
+        brief.market_making = brief.market_making || market_making;
+        brief
+    }
+
+    /// The body of the first section with this title, ignoring case.
+    pub fn section(&self, title: &str) -> Option<&str> {
+        self.sections
+            .iter()
+            .find(|s| s.title.eq_ignore_ascii_case(title))
+            .map(|s| s.body.as_str())
+    }
+
+    fn read(&mut self, sentence: &[String]) {
+        let has = |w: &str| sentence.iter().any(|s| s == w);
+        let has_prefix = |p: &str| sentence.iter().any(|s| s.starts_with(p));
+        if (has("market") && (has_prefix("maker") || has("making"))) || has("liquidity") {
+            self.market_making = true;
+        }
+        let limits = has("position") &&
+                     ["exceed", "above", "over", "beyond", "limit", "maximum", "more"]
+            .iter()
+            .any(|w| has_prefix(w));
+        for (i, word) in sentence.iter().enumerate() {
+            let n = match count(word) {
+                Some(n) => n,
+                None => continue,
+            };
+            if !sentence.get(i + 1).is_some_and(|w| w.starts_with("share")) {
+                continue;
+            }
+            if limits {
+                if self.position_limit.is_none() {
+                    self.position_limit = Some(Qty(n));
+                }
+                continue;
+            }
+            let before = &sentence[i.saturating_sub(4)..i];
+            let verb = before.iter().rev().filter_map(|w| verb(w)).next();
+            if let (Some(d), None) = (verb, self.target_qty) {
+                self.direction = Some(d);
+                self.target_qty = Some(Qty(n));
+            }
+        }
+        if has("profit") || has("nav") {
+            if let Some(cents) = sentence.iter().filter_map(|w| dollars(w)).next() {
+                if self.target_nav.is_none() {
+                    self.target_nav = Some(cents);
+                }
+            }
+        }
+    }
+}
+
+/// Split markdown at its headings, the part before the first heading
+/// going under `title`.
+fn sections(title: &str, body: &str) -> Vec<Section> {
+    let mut found = Vec::new();
+    let mut current = Section {
+        title: title.to_owned(),
+        body: String::new(),
+    };
+    for line in body.lines() {
+        if line.starts_with('#') {
+            if !current.body.trim().is_empty() {
+                found.push(current);
+            }
+            current = Section {
+                title: line.trim_start_matches('#').trim().to_owned(),
+                body: String::new(),
+            };
+        } else {
+            current.body.push_str(line);
+            current.body.push('\n');
+        }
+    }
+    if !current.body.trim().is_empty() {
+        found.push(current);
+    }
+    for s in &mut found {
+        s.body = s.body.trim().to_owned();
+    }
+    found
+}
+
+/// The text as sentences of lowercase words, without markdown emphasis or
+/// punctuation around the words.
+fn sentences(text: &str) -> Vec<Vec<String>> {
+    let plain: String = text.chars().filter(|c| !"*_`".contains(*c)).collect();
+    plain.split(['\n', '!', '?'])
+        .flat_map(|line| line.split(". "))
+        .map(|s| {
+            s.split_whitespace()
+                .map(|w| {
+                    w.trim_matches(|c: char| !(c.is_alphanumeric() || c == '$'))
+                        .to_lowercase()
+                })
+                .filter(|w| !w.is_empty())
+                .collect::<Vec<String>>()
+        })
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+fn verb(word: &str) -> Option<OrderDirection> {
+    match word {
+        "buy" | "purchase" | "acquire" | "accumulate" => Some(OrderDirection::Buy),
+        "sell" | "dump" | "unload" | "liquidate" => Some(OrderDirection::Sell),
+        _ => None,
+    }
+}
+
+/// A plain count like `100,000`.
+fn count(word: &str) -> Option<u64> {
+    if word.is_empty() || !word.chars().all(|c| c.is_ascii_digit() || c == ',') {
+        return None;
+    }
+    word.replace(',', "").parse().ok()
+}
+
+/// A whole dollar amount like `$250,000`, in cents.
+fn dollars(word: &str) -> Option<i64> {
+    let amount = match word.strip_prefix('$') {
+        Some(a) => a.split('.').next().unwrap_or(""),
+        None => return None,
+    };
+    count(amount).map(|d| d as i64 * 100)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data::{Level, OrderDirection};
+    use std::collections::HashMap;
+    use types::Qty;
+
+    fn level(instructions: &[(&str, &str)]) -> Level {
+        Level {
+            instructions: instructions.iter()
+                .map(|&(k, v)| (k.to_owned(), v.to_owned()))
+                .collect::<HashMap<String, String>>(),
+            ..Level::default()
+        }
+    }
+
+    #[test]
+    fn test_buy_target() {
+        let l = level(&[("Instructions",
+                         "Hi there! We'd like you to **purchase 100,000 shares** of BARF on \
+                          the TESTEX venue. Take care not to move the price."),
+                        ("Order Types", "Limit orders sit on the book.")]);
+        let b = LevelBrief::parse(&l);
+        assert!(matches!(b.direction, Some(OrderDirection::Buy)));
+        assert_eq!(Some(Qty(100000)), b.target_qty);
+        assert_eq!(None, b.position_limit);
+        assert!(!b.market_making);
+        assert_eq!(vec!["Instructions", "Order Types"],
+                   b.sections.iter().map(|s| s.title.as_str()).collect::<Vec<_>>());
+        assert_eq!(Some("Limit orders sit on the book."), b.section("order types"));
+    }
+
+    #[test]
+    fn test_market_maker() {
+        let l = level(&[("Instructions",
+                         "You are a market maker now.\n\n# Rules\n\nDon't let your position \
+                          exceed 1,000 shares, long or short. Reach a profit of $10,000.")]);
+        let b = LevelBrief::parse(&l);
+        assert!(b.market_making);
+        assert!(b.direction.is_none());
+        assert_eq!(Some(Qty(1000)), b.position_limit);
+        assert_eq!(Some(1000000), b.target_nav);
+        assert_eq!(Some("You are a market maker now."), b.section("Instructions"));
+        assert!(b.section("rules").unwrap().starts_with("Don't"));
+    }
+
+    #[test]
+    fn test_known_level_fills_gaps() {
+        let b = LevelBrief::for_level("chock_a_block", &level(&[]));
+        assert!(matches!(b.direction, Some(OrderDirection::Buy)));
+        assert_eq!(Some(Qty(100000)), b.target_qty);
+        let b = LevelBrief::for_level("first_steps",
+                                      &level(&[("Instructions", "Sell 5 shares.")]));
+        assert!(matches!(b.direction, Some(OrderDirection::Sell)));
+        assert_eq!(Some(Qty(5)), b.target_qty);
+        let b = LevelBrief::for_level("irrational_exuberance", &level(&[]));
+        assert_eq!(Some(Qty(1000)), b.position_limit);
+        assert!(!b.market_making);
+    }
+}
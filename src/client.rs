@@ -1,19 +1,26 @@
+use config::Config;
+use endpoint::{ApiProfile, Endpoint};
 use error::Result;
 use http::HttpClient;
 use http::AuthHttpClient;
+#[cfg(feature = "metrics")]
+use metrics;
+use order::OrderBuilder;
 use serde::Deserialize;
 use serde_json;
-use data::{HeartBeatResponse, Level, Order, OrderResponse, OrderbookResponse, QuoteResponse,
-           StockListResponse, VenueHeartBeatResponse, parse_response};
-
-static VENUE_URL: &'static str = "/ob/api/venues/";
-static HEARTBEAT_URL: &'static str = "/ob/api/heartbeat";
+use std::time::Instant;
+use tracing::field;
+use types::OrderId;
+use data::{AccountOrdersResponse, HeartBeatResponse, InstanceStatusResponse, Level, Order,
+           OrderResponse, OrderbookResponse, QuoteResponse, StockListResponse, StopLevelResponse,
+           VenueHeartBeatResponse, parse_response};
 
 /// Client for starting a new level of Stockfighter.
 #[derive(Debug, Clone)]
 pub struct Client<T: HttpClient + Clone> {
     http_client: T,
     base_url: String,
+    api: ApiProfile,
 }
 
 impl<T: HttpClient + Clone> Client<T> {
@@ -23,24 +30,103 @@ impl<T: HttpClient + Clone> Client<T> {
     /// if there is already a level ongoing.
     pub fn start_level(&self, level: &str) -> Result<LevelClient<T>> {
         // Start a level
-        let url = self.base_url.to_owned() + "/gm/levels/" + level;
-        let res = try!(self.http_client.post(&url, None));
-        let level: Level = try!(parse_response(&res));
+        let level: Level = try!(self.do_post(Endpoint::StartLevel { level: level.to_owned() }));
         // Give it back.
-        Ok(LevelClient::new(self.http_client.clone(), level, &self.base_url))
+        Ok(self.level_client(level))
+    }
+
+    /// Restart a level instance from the beginning.
+    pub fn restart_level(&self, instance_id: i64) -> Result<LevelClient<T>> {
+        let level: Level = try!(self.do_post(Endpoint::RestartLevel { instance: instance_id }));
+        Ok(self.level_client(level))
+    }
+
+    /// Tear down a level instance.
+    pub fn stop_level(&self, instance_id: i64) -> Result<StopLevelResponse> {
+        self.do_post(Endpoint::StopLevel { instance: instance_id })
+    }
+
+    /// Ask the game master how a level instance is doing.
+    pub fn instance_status(&self, instance_id: i64) -> Result<InstanceStatusResponse> {
+        let endpoint = Endpoint::InstanceStatus { instance: instance_id };
+        request(&self.base_url,
+                &self.api,
+                "GET",
+                endpoint,
+                |url| self.http_client.get(url))
+    }
+
+    /// Construct a Client that sends everything through `http_client`, e.g.
+    /// a `middleware::Stack`.
+    pub fn with_http_client(http_client: T, base_url: &str, api: ApiProfile) -> Client<T> {
+        Client {
+            http_client: http_client,
+            base_url: base_url.to_owned(),
+            api: api,
+        }
+    }
+
+    /// Construct a level client for a level that has already been started,
+    /// using this client's credentials.
+    pub fn level_client(&self, level: Level) -> LevelClient<T> {
+        LevelClient::new(self.http_client.clone(), level, &self.base_url, self.api.clone())
+    }
+
+    fn do_post<D: Deserialize>(&self, endpoint: Endpoint) -> Result<D> {
+        request(&self.base_url,
+                &self.api,
+                "POST",
+                endpoint,
+                |url| self.http_client.post(url, None))
     }
 }
 
 impl Client<AuthHttpClient> {
     /// Given an api key construct a new Client that can interact with stockfighter's game api.
     pub fn new(api_key: &str) -> Client<AuthHttpClient> {
-        Client::new_with_url(api_key, "https://api.stockfighter.io")
+        Client::new_with_url(api_key, "https://api.stockfighter.io", ApiProfile::default())
     }
-    pub fn new_with_url(api_key: &str, base_url: &str) -> Client<AuthHttpClient> {
+    /// Construct a Client for a Stockfighter compatible server laid out
+    /// like `api`.
+    pub fn new_with_url(api_key: &str, base_url: &str, api: ApiProfile) -> Client<AuthHttpClient> {
+        let mut http_client = AuthHttpClient::new(api_key);
+        http_client.set_auth_header(&api.auth_header);
         Client {
-            http_client: AuthHttpClient::new(api_key),
+            http_client: http_client,
             base_url: base_url.to_owned(),
+            api: api,
+        }
+    }
+    /// Construct a Client from the config file, environment and built in
+    /// defaults. See `Config` for where each setting comes from.
+    pub fn from_config() -> Result<Client<AuthHttpClient>> {
+        let config = try!(Config::load());
+        Client::with_config(&config)
+    }
+    /// Construct a Client from an already loaded config.
+    ///
+    /// # Errors
+    ///
+    /// Errors out when:
+    ///  the config's `ca_file` can't be loaded
+    pub fn with_config(config: &Config) -> Result<Client<AuthHttpClient>> {
+        let mut builder = AuthHttpClient::builder()
+            .api_key(config.api_key.expose())
+            .auth_header(&config.api.auth_header)
+            .connect_timeout(config.connect_timeout)
+            .read_timeout(config.read_timeout)
+            .write_timeout(config.write_timeout);
+        if let Some(ref ca) = config.ca_file {
+            builder = builder.add_root_certificate(ca.clone());
+        }
+        if let Some((ref host, port)) = config.proxy {
+            builder = builder.http_proxy(host, port);
         }
+        Ok(Client {
+            http_client: try!(builder.build()),
+            base_url: config.base_url.to_owned(),
+            api: config.api.clone(),
+        })
     }
 }
 
@@ -53,6 +139,7 @@ pub struct LevelClient<T: HttpClient + Clone> {
     http_client: T,
     pub level: Level,
     pub base_url: String,
+    pub api: ApiProfile,
 }
 
 impl<T: HttpClient + Clone> LevelClient<T> {
@@ -61,14 +148,12 @@ impl<T: HttpClient + Clone> LevelClient<T> {
     /// This should really only be used to sanity check that the level
     /// hasn't been torn down.
     pub fn heart_beat(&self) -> Result<HeartBeatResponse> {
-        let url = self.base_url.to_owned() + HEARTBEAT_URL;
-        self.do_get(&url)
+        self.do_get(Endpoint::Heartbeat)
     }
 
     /// Check if a venue is ok.
     pub fn venue_heart_beat(&self, venue: &str) -> Result<VenueHeartBeatResponse> {
-        let url = self.base_url.to_owned() + VENUE_URL + venue + "/heartbeat";
-        self.do_get(&url)
+        self.do_get(Endpoint::VenueHeartbeat { venue: venue.into() })
     }
 
     /// Get a list of all the stocks this venue can accept trades for.
@@ -79,75 +164,202 @@ impl<T: HttpClient + Clone> LevelClient<T> {
     ///  http fails
     ///  parsing fails
     pub fn stock_list(&self, venue: &str) -> Result<StockListResponse> {
-        let url = self.base_url.to_owned() + VENUE_URL + venue + "/stocks";
-        self.do_get(&url)
+        self.do_get(Endpoint::Stocks { venue: venue.into() })
     }
 
     /// Get a copy of the venue's order book. Stockfighter suggests
     /// that this will be a slow operation that should be done
     /// as little as possible.
     pub fn orderbook(&self, venue: &str, stock: &str) -> Result<OrderbookResponse> {
-        let url = self.base_url.to_owned() + VENUE_URL + venue + "/stocks/" + stock;
-        self.do_get(&url)
+        self.do_get(Endpoint::Orderbook {
+            venue: venue.into(),
+            stock: stock.into(),
+        })
     }
 
     /// Ask a venue about the current state of a stock.
     pub fn quote(&self, venue: &str, stock: &str) -> Result<QuoteResponse> {
-        let url = self.base_url.to_owned() + VENUE_URL + venue + "/stocks/" + stock + "/quote";
-        self.do_get(&url)
+        self.do_get(Endpoint::Quote {
+            venue: venue.into(),
+            stock: stock.into(),
+        })
     }
 
 
+    /// Start building a buy order for this level's account.
+    pub fn buy(&self) -> OrderBuilder {
+        Order::builder(&self.level).buy()
+    }
+
+    /// Start building a sell order for this level's account.
+    pub fn sell(&self) -> OrderBuilder {
+        Order::builder(&self.level).sell()
+    }
+
     /// Send in an order, and get back a response.
     pub fn order(&self, o: &Order) -> Result<OrderResponse> {
-        let url = self.base_url.to_owned() + VENUE_URL + &o.venue + "/stocks/" + &o.stock +
-                  "/orders";
-        debug!("Placing  {:?}", o);
         let encoded = try!(serde_json::to_string(o));
-        let res = try!(self.http_client.post(&url, Some(&encoded)));
-        parse_response(&res)
+        let res: Result<OrderResponse> = self.do_post(Endpoint::Orders {
+                                                           venue: o.venue.clone(),
+                                                           stock: o.stock.clone(),
+                                                       },
+                                                       &encoded);
+        if let Ok(ref r) = res {
+            info!(venue = %r.venue,
+                  stock = %r.symbol,
+                  order_id = r.id.0,
+                  direction = ?r.direction,
+                  order_type = ?r.order_type,
+                  price = r.price.0,
+                  qty = r.original_qty.0,
+                  filled = r.total_filled.0,
+                  "placed order");
+            #[cfg(feature = "metrics")]
+            metrics::global().record_fills(r);
+        }
+        res
     }
 
     /// Find out how a specific order on a specific venue is doing.
-    pub fn order_status(&self, venue: &str, stock: &str, id: u64) -> Result<OrderResponse> {
-        let url = self.base_url.to_owned() + VENUE_URL + venue + "/stocks/" + stock + "/orders/" +
-                  &id.to_string();
-        self.do_get(&url)
+    pub fn order_status(&self, venue: &str, stock: &str, id: OrderId) -> Result<OrderResponse> {
+        let status: Result<OrderResponse> = self.do_get(Endpoint::Order {
+            venue: venue.into(),
+            stock: stock.into(),
+            id: id,
+        });
+        #[cfg(feature = "metrics")]
+        {
+            if let Ok(ref r) = status {
+                metrics::global().record_fills(r);
+            }
+        }
+        status
     }
 
     /// Try and cancel an order.
-    pub fn delete_order(&self, venue: &str, stock: &str, id: u64) -> Result<OrderResponse> {
-        let url = self.base_url.to_owned() + VENUE_URL + venue + "/stocks/" + stock + "/orders/" +
-                  &id.to_string();
-        debug!("Cacneling Order {} for Stock {} at Venue {}",
-               id,
-               stock,
-               venue);
-        let status = self.do_delete::<OrderResponse>(&url);
-        debug!("Cancled Order  {:?}", status);
+    pub fn delete_order(&self, venue: &str, stock: &str, id: OrderId) -> Result<OrderResponse> {
+        let status: Result<OrderResponse> = self.do_delete(Endpoint::Order {
+            venue: venue.into(),
+            stock: stock.into(),
+            id: id,
+        });
+        if let Ok(ref r) = status {
+            info!(venue = %r.venue,
+                  stock = %r.symbol,
+                  order_id = r.id.0,
+                  filled = r.total_filled.0,
+                  "canceled order");
+            #[cfg(feature = "metrics")]
+            metrics::global().record_fills(r);
+        }
         status
     }
 
-    fn do_get<D: Deserialize>(&self, url: &str) -> Result<D> {
-        let res = try!(self.http_client.get(url));
-        parse_response(&res)
+    /// List every order this level's account has placed on a venue.
+    pub fn orders(&self, venue: &str) -> Result<AccountOrdersResponse> {
+        let res: Result<AccountOrdersResponse> = self.do_get(Endpoint::AccountOrders {
+            venue: venue.into(),
+            account: self.level.account.clone(),
+        });
+        #[cfg(feature = "metrics")]
+        {
+            if let Ok(ref r) = res {
+                metrics::global().record_orders(&r.venue, None, &r.orders);
+                for o in &r.orders {
+                    metrics::global().record_fills(o);
+                }
+            }
+        }
+        res
+    }
+
+    /// List every order this level's account has placed for one stock on a venue.
+    pub fn stock_orders(&self, venue: &str, stock: &str) -> Result<AccountOrdersResponse> {
+        let res: Result<AccountOrdersResponse> = self.do_get(Endpoint::AccountStockOrders {
+            venue: venue.into(),
+            account: self.level.account.clone(),
+            stock: stock.into(),
+        });
+        #[cfg(feature = "metrics")]
+        {
+            if let Ok(ref r) = res {
+                metrics::global().record_orders(&r.venue, Some(stock), &r.orders);
+                for o in &r.orders {
+                    metrics::global().record_fills(o);
+                }
+            }
+        }
+        res
+    }
+
+    fn do_get<D: Deserialize>(&self, endpoint: Endpoint) -> Result<D> {
+        request(&self.base_url,
+                &self.api,
+                "GET",
+                endpoint,
+                |url| self.http_client.get(url))
+    }
+    fn do_delete<D: Deserialize>(&self, endpoint: Endpoint) -> Result<D> {
+        request(&self.base_url,
+                &self.api,
+                "DELETE",
+                endpoint,
+                |url| self.http_client.delete(url))
     }
-    fn do_delete<D: Deserialize>(&self, url: &str) -> Result<D> {
-        let res = try!(self.http_client.delete(url));
-        parse_response(&res)
+    fn do_post<D: Deserialize>(&self, endpoint: Endpoint, body: &str) -> Result<D> {
+        request(&self.base_url,
+                &self.api,
+                "POST",
+                endpoint,
+                |url| self.http_client.post(url, Some(body)))
     }
 
 
     /// Constructs a new level client.
-    pub fn new(http_client: T, level: Level, base_url: &str) -> LevelClient<T> {
+    pub fn new(http_client: T, level: Level, base_url: &str, api: ApiProfile) -> LevelClient<T> {
         LevelClient {
             http_client: http_client.clone(),
             level: level,
             base_url: base_url.to_owned(),
+            api: api,
         }
     }
 }
 
+/// Send one request and parse the response, inside a `request` span
+/// carrying the endpoint, venue, stock, order id and how long it took.
+fn request<D, F>(base_url: &str,
+                 api: &ApiProfile,
+                 method: &'static str,
+                 endpoint: Endpoint,
+                 send: F)
+                 -> Result<D>
+    where D: Deserialize,
+          F: FnOnce(&str) -> Result<String>
+{
+    let url = base_url.to_owned() + &endpoint.path_in(api);
+    let span = info_span!("request",
+                          method = method,
+                          endpoint = endpoint.name(),
+                          venue = endpoint.venue().map(|v| v.as_str()),
+                          stock = endpoint.stock().map(|s| s.as_str()),
+                          order_id = endpoint.order_id().map(|id| id.0),
+                          latency_ms = field::Empty);
+    let _enter = span.enter();
+    #[cfg(feature = "metrics")]
+    let timer = metrics::global().start(endpoint.name(), method);
+    let start = Instant::now();
+    let res = send(&url).and_then(|body| parse_response(&body));
+    let elapsed = start.elapsed();
+    span.record("latency_ms", elapsed.as_secs() * 1000 + elapsed.subsec_millis() as u64);
+    if let Err(ref e) = res {
+        warn!(kind = e.kind(), error = %e, "request failed");
+    }
+    #[cfg(feature = "metrics")]
+    timer.finish(res.as_ref().err());
+    res
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -182,6 +394,7 @@ mod tests {
         let c = Client {
             http_client: TestHttpClient { post_result: json_resp.to_string() },
             base_url: "http://localhost:8000".to_owned(),
+            api: ApiProfile::default(),
         };
         c.start_level("test").unwrap();
     }
@@ -191,15 +404,16 @@ mod tests {
         let level = Level {
             ok: true,
             instance_id: 1090,
-            account: "myac".to_string(),
+            account: "myac".into(),
             instructions: HashMap::new(),
-            tickers: vec!["test".to_string()],
-            venues: vec!["ven".to_string()],
+            tickers: vec!["test".into()],
+            venues: vec!["ven".into()],
         };
         let json_resp = serde_json::to_string(&level).unwrap();
         let c = Client {
             http_client: TestHttpClient { post_result: json_resp.to_string() },
             base_url: "http://localhost:8000".to_owned(),
+            api: ApiProfile::default(),
         };
         c.start_level("test").unwrap();
     }
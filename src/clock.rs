@@ -0,0 +1,209 @@
+//! Relate the venue's clock to ours.
+//!
+//! Responses are stamped with the server's time (`quote_time`, `ts`), which
+//! can be well off from the local clock. `ClockSync` estimates the offset
+//! between the two and the round trip time from requests we've timed, so a
+//! quote's age or an order's latency can be worked out on one clock.
+
+use chrono::{DateTime, Duration, UTC};
+use data::{OrderResponse, OrderbookResponse, QuoteResponse};
+use error::Result;
+use std::collections::VecDeque;
+
+/// A response that carries the server's time.
+pub trait Stamped {
+    fn server_time(&self) -> Option<DateTime<UTC>>;
+}
+
+impl Stamped for QuoteResponse {
+    fn server_time(&self) -> Option<DateTime<UTC>> {
+        self.quote_time
+    }
+}
+
+impl Stamped for OrderbookResponse {
+    fn server_time(&self) -> Option<DateTime<UTC>> {
+        Some(self.ts)
+    }
+}
+
+impl Stamped for OrderResponse {
+    fn server_time(&self) -> Option<DateTime<UTC>> {
+        Some(self.ts)
+    }
+}
+
+/// One timed request.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    /// Server time minus local time, assuming the server stamped the
+    /// response halfway through the round trip.
+    pub offset: Duration,
+    pub rtt: Duration,
+}
+
+impl Sample {
+    pub fn new(sent: DateTime<UTC>, server: DateTime<UTC>, received: DateTime<UTC>) -> Sample {
+        let rtt = received.signed_duration_since(sent);
+        let midpoint = sent + rtt / 2;
+        Sample {
+            offset: server.signed_duration_since(midpoint),
+            rtt: rtt,
+        }
+    }
+}
+
+/// Estimates the server's clock offset and the round trip time from the
+/// last few timed requests.
+///
+/// The offset comes from the sample with the shortest round trip, which
+/// has the least room for error. A quote's `quote_time` is when the quote
+/// last changed rather than when it was sent, so an idle book can make the
+/// server look behind; the freshest stamps, like an order's `ts`, make the
+/// best samples.
+#[derive(Debug, Clone)]
+pub struct ClockSync {
+    len: usize,
+    samples: VecDeque<Sample>,
+}
+
+impl Default for ClockSync {
+    fn default() -> ClockSync {
+        ClockSync::new(32)
+    }
+}
+
+impl ClockSync {
+    /// Keep the last `len` samples.
+    pub fn new(len: usize) -> ClockSync {
+        ClockSync {
+            len: len.max(1),
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Add a request sent and answered at these local times, with the
+    /// server's stamp on the response.
+    pub fn observe(&mut self,
+                   sent: DateTime<UTC>,
+                   server: DateTime<UTC>,
+                   received: DateTime<UTC>)
+                   -> Sample {
+        let sample = Sample::new(sent, server, received);
+        self.samples.push_back(sample);
+        if self.samples.len() > self.len {
+            self.samples.pop_front();
+        }
+        sample
+    }
+
+    /// Add a response, if it has a server time.
+    pub fn observe_response<S: Stamped>(&mut self,
+                                        sent: DateTime<UTC>,
+                                        response: &S,
+                                        received: DateTime<UTC>)
+                                        -> Option<Sample> {
+        response.server_time().map(|server| self.observe(sent, server, received))
+    }
+
+    /// Make a request, timing it to add a sample.
+    /// ```no_run
+    /// # use michromer::clock::ClockSync;
+    /// # fn f(client: &michromer::client::LevelClient<michromer::http::AuthHttpClient>) {
+    /// let mut sync = ClockSync::default();
+    /// let quote = sync.time(|| client.quote("TESTEX", "FOOBAR")).unwrap();
+    /// println!("{:?} old", sync.age(quote.quote_time.unwrap()));
+    /// # }
+    /// ```
+    pub fn time<S, F>(&mut self, request: F) -> Result<S>
+        where S: Stamped,
+              F: FnOnce() -> Result<S>
+    {
+        let sent = UTC::now();
+        let response = try!(request());
+        self.observe_response(sent, &response, UTC::now());
+        Ok(response)
+    }
+
+    /// How far the server's clock is ahead of ours.
+    pub fn offset(&self) -> Option<Duration> {
+        self.samples.iter().min_by_key(|s| s.rtt).map(|s| s.offset)
+    }
+
+    /// The average round trip time.
+    pub fn rtt(&self) -> Option<Duration> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let total = self.samples.iter().fold(Duration::zero(), |t, s| t + s.rtt);
+        Some(total / self.samples.len() as i32)
+    }
+
+    /// The shortest round trip time.
+    pub fn min_rtt(&self) -> Option<Duration> {
+        self.samples.iter().map(|s| s.rtt).min()
+    }
+
+    /// The time on the server's clock at a local time. Until there are
+    /// samples the clocks are taken to agree.
+    pub fn to_server(&self, local: DateTime<UTC>) -> DateTime<UTC> {
+        local + self.offset().unwrap_or_else(Duration::zero)
+    }
+
+    /// The time on our clock at a server time.
+    pub fn to_local(&self, server: DateTime<UTC>) -> DateTime<UTC> {
+        server - self.offset().unwrap_or_else(Duration::zero)
+    }
+
+    /// The time on the server's clock right now.
+    pub fn server_now(&self) -> DateTime<UTC> {
+        self.to_server(UTC::now())
+    }
+
+    /// How old something stamped by the server was at a local time.
+    pub fn age_at(&self, server: DateTime<UTC>, local: DateTime<UTC>) -> Duration {
+        self.to_server(local).signed_duration_since(server)
+    }
+
+    /// How old something stamped by the server is now.
+    pub fn age(&self, server: DateTime<UTC>) -> Duration {
+        self.age_at(server, UTC::now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Duration, UTC};
+
+    fn at(millis: i64) -> DateTime<UTC> {
+        let start: DateTime<UTC> = "2016-06-02T16:20:00Z".parse().unwrap();
+        start + Duration::milliseconds(millis)
+    }
+
+    #[test]
+    fn test_offset_from_fastest_sample() {
+        let mut sync = ClockSync::new(3);
+        assert_eq!(None, sync.offset());
+        assert_eq!(at(500), sync.to_local(at(500)));
+        // Server is 2s ahead; this one came back slowly on one leg.
+        sync.observe(at(0), at(2300), at(400));
+        // A quick one.
+        let s = sync.observe(at(1000), at(3050), at(1100));
+        assert_eq!(Duration::milliseconds(2000), s.offset);
+        assert_eq!(Some(Duration::milliseconds(2000)), sync.offset());
+        assert_eq!(Some(Duration::milliseconds(250)), sync.rtt());
+        assert_eq!(Some(Duration::milliseconds(100)), sync.min_rtt());
+        assert_eq!(at(1000), sync.to_local(at(3000)));
+        // Stamped at server time 4000, i.e. local 2000, and seen at local 2500.
+        assert_eq!(Duration::milliseconds(500), sync.age_at(at(4000), at(2500)));
+    }
+
+    #[test]
+    fn test_window() {
+        let mut sync = ClockSync::new(1);
+        sync.observe(at(0), at(100), at(100));
+        sync.observe(at(0), at(-700), at(400));
+        assert_eq!(Some(Duration::milliseconds(-900)), sync.offset());
+    }
+}
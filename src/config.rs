@@ -0,0 +1,470 @@
+use endpoint::ApiProfile;
+use error::{Error, Result};
+use secret::{self, Secret};
+use std::env;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use toml;
+use zeroize::Zeroize;
+
+/// Profile for the real Stockfighter servers.
+pub static LIVE_PROFILE: &'static str = "live";
+/// Profile for a simulator running on this machine.
+pub static SIM_PROFILE: &'static str = "sim";
+
+static LIVE_URL: &'static str = "https://api.stockfighter.io";
+static SIM_URL: &'static str = "http://127.0.0.1:8000";
+static SIM_WS_BASE: &'static str = "ws://127.0.0.1:8000/ob/api/ws";
+
+static PROFILE_ENV: &'static str = "MICHROMER_PROFILE";
+static CONFIG_ENV: &'static str = "MICHROMER_CONFIG";
+static KEY_ENV: &'static str = "MICHROMER_API_KEY";
+static KEY_FILE_ENV: &'static str = "MICHROMER_API_KEY_FILE";
+static URL_ENV: &'static str = "MICHROMER_BASE_URL";
+static VENUE_ENV: &'static str = "MICHROMER_VENUE";
+static ACCOUNT_ENV: &'static str = "MICHROMER_ACCOUNT";
+static READ_TIMEOUT_ENV: &'static str = "MICHROMER_READ_TIMEOUT_MS";
+static WRITE_TIMEOUT_ENV: &'static str = "MICHROMER_WRITE_TIMEOUT_MS";
+static CONNECT_TIMEOUT_ENV: &'static str = "MICHROMER_CONNECT_TIMEOUT_MS";
+static CA_FILE_ENV: &'static str = "MICHROMER_CA_FILE";
+static PROXY_ENV: &'static str = "MICHROMER_PROXY";
+
+/// One layer of settings. Anything left as `None` is filled in from the
+/// layer below it.
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    pub profile: Option<String>,
+    pub api_key: Option<Secret>,
+    /// A file to read the api key from, if `api_key` isn't set. It's only
+    /// read if no higher layer has a key.
+    pub api_key_file: Option<PathBuf>,
+    pub base_url: Option<String>,
+    pub venue: Option<String>,
+    pub account: Option<String>,
+    pub read_timeout: Option<Duration>,
+    pub write_timeout: Option<Duration>,
+    pub connect_timeout: Option<Duration>,
+    /// PEM file of extra root certificates to trust.
+    pub ca_file: Option<PathBuf>,
+    /// Http proxy, as `host:port`.
+    pub proxy: Option<String>,
+    pub order_book_prefix: Option<String>,
+    pub game_master_prefix: Option<String>,
+    pub auth_header: Option<String>,
+    pub ws_base: Option<String>,
+}
+
+impl Settings {
+    /// Use these settings, falling back to `other` for anything not set.
+    pub fn or(self, other: Settings) -> Settings {
+        // A key or key file in this layer hides both in the one below.
+        let (api_key, api_key_file) = if self.api_key.is_some() || self.api_key_file.is_some() {
+            (self.api_key, self.api_key_file)
+        } else {
+            (other.api_key, other.api_key_file)
+        };
+        Settings {
+            profile: self.profile.or(other.profile),
+            api_key: api_key,
+            api_key_file: api_key_file,
+            base_url: self.base_url.or(other.base_url),
+            venue: self.venue.or(other.venue),
+            account: self.account.or(other.account),
+            read_timeout: self.read_timeout.or(other.read_timeout),
+            write_timeout: self.write_timeout.or(other.write_timeout),
+            connect_timeout: self.connect_timeout.or(other.connect_timeout),
+            ca_file: self.ca_file.or(other.ca_file),
+            proxy: self.proxy.or(other.proxy),
+            order_book_prefix: self.order_book_prefix.or(other.order_book_prefix),
+            game_master_prefix: self.game_master_prefix.or(other.game_master_prefix),
+            auth_header: self.auth_header.or(other.auth_header),
+            ws_base: self.ws_base.or(other.ws_base),
+        }
+    }
+
+    /// Read the `MICHROMER_*` environment variables.
+    pub fn from_env() -> Result<Settings> {
+        Ok(Settings {
+            profile: env::var(PROFILE_ENV).ok(),
+            api_key: env::var(KEY_ENV).ok().map(|k| Secret::from(k.trim())),
+            api_key_file: env::var(KEY_FILE_ENV).ok().map(PathBuf::from),
+            base_url: env::var(URL_ENV).ok(),
+            venue: env::var(VENUE_ENV).ok(),
+            account: env::var(ACCOUNT_ENV).ok(),
+            read_timeout: try!(env_millis(READ_TIMEOUT_ENV)),
+            write_timeout: try!(env_millis(WRITE_TIMEOUT_ENV)),
+            connect_timeout: try!(env_millis(CONNECT_TIMEOUT_ENV)),
+            ca_file: env::var(CA_FILE_ENV).ok().map(PathBuf::from),
+            proxy: env::var(PROXY_ENV).ok(),
+            ..Settings::default()
+        })
+    }
+
+    /// Read the settings for a profile out of a config file.
+    ///
+    /// Keys at the top of the file apply to every profile, and keys in a
+    /// `[profiles.<name>]` table override them for that profile.
+    pub fn from_toml(buf: &str, profile: &str) -> Result<Settings> {
+        let root: toml::Value = try!(buf.parse());
+        let root = try!(root.as_table().ok_or_else(|| config_error("config isn't a table")));
+        let mut settings = try!(Settings::from_table(root));
+        let section = root.get("profiles")
+            .and_then(|p| p.as_table())
+            .and_then(|p| p.get(profile));
+        if let Some(section) = section {
+            let table = try!(section.as_table()
+                .ok_or_else(|| config_error(&format!("profile {} isn't a table", profile))));
+            settings = try!(Settings::from_table(table)).or(settings);
+        }
+        Ok(settings)
+    }
+
+    /// Settings every known profile starts with.
+    pub fn builtin(profile: &str) -> Option<Settings> {
+        if profile == LIVE_PROFILE {
+            Some(Settings { base_url: Some(LIVE_URL.to_owned()), ..Settings::default() })
+        } else if profile == SIM_PROFILE {
+            // Local simulators don't check the key.
+            Some(Settings {
+                api_key: Some(Secret::from("")),
+                base_url: Some(SIM_URL.to_owned()),
+                ws_base: Some(SIM_WS_BASE.to_owned()),
+                ..Settings::default()
+            })
+        } else {
+            None
+        }
+    }
+
+    fn from_table(table: &toml::value::Table) -> Result<Settings> {
+        Ok(Settings {
+            profile: try!(toml_string(table, "profile")),
+            api_key: try!(toml_string(table, "api_key")).map(Secret::from),
+            api_key_file: try!(toml_string(table, "api_key_file")).map(PathBuf::from),
+            base_url: try!(toml_string(table, "base_url")),
+            venue: try!(toml_string(table, "venue")),
+            account: try!(toml_string(table, "account")),
+            read_timeout: try!(toml_millis(table, "read_timeout_ms")),
+            write_timeout: try!(toml_millis(table, "write_timeout_ms")),
+            connect_timeout: try!(toml_millis(table, "connect_timeout_ms")),
+            ca_file: try!(toml_string(table, "ca_file")).map(PathBuf::from),
+            proxy: try!(toml_string(table, "proxy")),
+            order_book_prefix: try!(toml_string(table, "order_book_prefix")),
+            game_master_prefix: try!(toml_string(table, "game_master_prefix")),
+            auth_header: try!(toml_string(table, "auth_header")),
+            ws_base: try!(toml_string(table, "ws_base")),
+        })
+    }
+}
+
+/// Everything needed to talk to a Stockfighter server.
+///
+/// Each value comes from, in order, settings passed in explicitly, the
+/// `MICHROMER_*` environment variables, the chosen profile in
+/// `~/.config/michromer/config.toml`, and the built in defaults for the
+/// `live` and `sim` profiles.
+///
+/// Servers that don't lay the api out like stockfighter.io can set
+/// `order_book_prefix`, `game_master_prefix`, `auth_header` and `ws_base`
+/// in the config file; see `ApiProfile`.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub profile: String,
+    pub api_key: Secret,
+    pub base_url: String,
+    pub venue: Option<String>,
+    pub account: Option<String>,
+    pub read_timeout: Option<Duration>,
+    pub write_timeout: Option<Duration>,
+    pub connect_timeout: Option<Duration>,
+    pub ca_file: Option<PathBuf>,
+    /// Http proxy host and port.
+    pub proxy: Option<(String, u16)>,
+    pub api: ApiProfile,
+}
+
+impl Config {
+    /// Load the config without any explicit settings.
+    pub fn load() -> Result<Config> {
+        Config::load_with(Settings::default())
+    }
+
+    /// Load the config, letting `explicit` override everything else.
+    ///
+    /// A missing config file is fine, but one that can't be read is an
+    /// error, and so is one with an `api_key` in it that other users can
+    /// read.
+    pub fn load_with(explicit: Settings) -> Result<Config> {
+        let env = try!(Settings::from_env());
+        let mut file = match Config::path() {
+            Some(p) => try!(read_config(&p)),
+            None => None,
+        };
+        let config = Config::resolve(explicit, env, file.as_ref().map(|s| s.as_ref()));
+        if let Some(ref mut buf) = file {
+            buf.zeroize();
+        }
+        config
+    }
+
+    /// Combine explicit settings, environment settings and the contents of
+    /// a config file.
+    pub fn resolve(explicit: Settings, env: Settings, file: Option<&str>) -> Result<Config> {
+        let top = match file {
+            Some(buf) => try!(Settings::from_toml(buf, "")),
+            None => Settings::default(),
+        };
+        let profile = explicit.profile
+            .clone()
+            .or(env.profile.clone())
+            .or(top.profile)
+            .unwrap_or_else(|| LIVE_PROFILE.to_owned());
+        let from_file = match file {
+            Some(buf) => try!(Settings::from_toml(buf, &profile)),
+            None => Settings::default(),
+        };
+        let builtin = Settings::builtin(&profile).unwrap_or_default();
+        let s = explicit.or(env).or(from_file).or(builtin);
+
+        let api_key = match (s.api_key, s.api_key_file) {
+            (Some(k), _) => k,
+            (None, Some(path)) => try!(Secret::from_file(path)),
+            (None, None) => {
+                return Err(config_error(&format!("no api key for profile {}", profile)));
+            }
+        };
+        let base_url = try!(s.base_url
+            .ok_or_else(|| config_error(&format!("no base url for profile {}", profile))));
+        let proxy = match s.proxy {
+            Some(p) => Some(try!(parse_proxy(&p))),
+            None => None,
+        };
+        let default_api = ApiProfile::default();
+        let api = ApiProfile {
+            order_book_prefix: s.order_book_prefix.unwrap_or(default_api.order_book_prefix),
+            game_master_prefix: s.game_master_prefix.unwrap_or(default_api.game_master_prefix),
+            auth_header: s.auth_header.unwrap_or(default_api.auth_header),
+            ws_base: s.ws_base.unwrap_or(default_api.ws_base),
+        };
+        Ok(Config {
+            profile: profile,
+            api_key: api_key,
+            base_url: base_url,
+            venue: s.venue,
+            account: s.account,
+            read_timeout: s.read_timeout,
+            write_timeout: s.write_timeout,
+            connect_timeout: s.connect_timeout,
+            ca_file: s.ca_file,
+            proxy: proxy,
+            api: api,
+        })
+    }
+
+    /// Where the config file lives. `MICHROMER_CONFIG` overrides the default
+    /// of `~/.config/michromer/config.toml`.
+    pub fn path() -> Option<PathBuf> {
+        if let Ok(p) = env::var(CONFIG_ENV) {
+            return Some(PathBuf::from(p));
+        }
+        env::var("HOME").ok().map(|h| PathBuf::from(h).join(".config/michromer/config.toml"))
+    }
+}
+
+/// Read a config file, or `None` if there isn't one. Refuses a file with
+/// an api key in it that other users can read.
+fn read_config(path: &Path) -> Result<Option<String>> {
+    let mut f = match File::open(path) {
+        Ok(f) => f,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut buf = String::new();
+    let res = f.read_to_string(&mut buf)
+        .map_err(Error::from)
+        .and_then(|_| if has_api_key(&buf) {
+            secret::check_permissions(path, &f)
+        } else {
+            Ok(())
+        });
+    match res {
+        Ok(()) => Ok(Some(buf)),
+        Err(e) => {
+            buf.zeroize();
+            Err(e)
+        }
+    }
+}
+
+/// Whether a config file has an `api_key`, at the top or in a profile.
+fn has_api_key(buf: &str) -> bool {
+    let root: toml::Value = match buf.parse() {
+        Ok(r) => r,
+        Err(_) => return false,
+    };
+    let has = |v: &toml::Value| v.get("api_key").is_some();
+    has(&root) ||
+    root.get("profiles").and_then(|p| p.as_table()).is_some_and(|p| p.values().any(has))
+}
+
+fn config_error(msg: &str) -> Error {
+    Error::Config(msg.to_owned())
+}
+
+/// Split a `host:port` proxy, ignoring any `http://` in front.
+fn parse_proxy(proxy: &str) -> Result<(String, u16)> {
+    let bad = || config_error(&format!("proxy {:?} should look like host:port", proxy));
+    let trimmed = proxy.trim().trim_start_matches("http://").trim_end_matches('/');
+    let colon = try!(trimmed.rfind(':').ok_or_else(&bad));
+    let port = try!(trimmed[colon + 1..].parse().map_err(|_| bad()));
+    let host = &trimmed[..colon];
+    if host.is_empty() {
+        return Err(bad());
+    }
+    Ok((host.to_owned(), port))
+}
+
+fn env_millis(name: &str) -> Result<Option<Duration>> {
+    match env::var(name) {
+        Ok(v) => {
+            v.trim()
+                .parse()
+                .map(|ms| Some(Duration::from_millis(ms)))
+                .map_err(|_| config_error(&format!("{} must be a number of milliseconds", name)))
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+fn toml_string(table: &toml::value::Table, key: &str) -> Result<Option<String>> {
+    match table.get(key) {
+        Some(v) => {
+            v.as_str()
+                .map(|s| Some(s.to_owned()))
+                .ok_or_else(|| config_error(&format!("{} must be a string", key)))
+        }
+        None => Ok(None),
+    }
+}
+
+fn toml_millis(table: &toml::value::Table, key: &str) -> Result<Option<Duration>> {
+    match table.get(key) {
+        Some(v) => {
+            match v.as_integer() {
+                Some(ms) if ms >= 0 => Ok(Some(Duration::from_millis(ms as u64))),
+                _ => Err(config_error(&format!("{} must be a number of milliseconds", key))),
+            }
+        }
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    static FILE: &'static str = "profile = \"sim\"
+account = \"EXB123456\"
+
+[profiles.live]
+api_key = \"live-key\"
+
+[profiles.sim]
+venue = \"TESTEX\"
+read_timeout_ms = 5000
+order_book_prefix = \"/api\"
+auth_header = \"X-Api-Key\"
+proxy = \"http://proxy.local:3128\"
+";
+
+    #[test]
+    fn test_builtin_sim() {
+        let c = Config::resolve(Settings::default(), Settings::default(), Some(FILE)).unwrap();
+        assert_eq!("sim", c.profile);
+        assert_eq!("", c.api_key.expose());
+        assert_eq!("http://127.0.0.1:8000", c.base_url);
+        assert_eq!(Some("TESTEX".to_owned()), c.venue);
+        assert_eq!(Some("EXB123456".to_owned()), c.account);
+        assert_eq!(Some(Duration::from_millis(5000)), c.read_timeout);
+        assert_eq!("/api", c.api.order_book_prefix);
+        assert_eq!("/gm", c.api.game_master_prefix);
+        assert_eq!("X-Api-Key", c.api.auth_header);
+        assert_eq!("ws://127.0.0.1:8000/ob/api/ws", c.api.ws_base);
+        assert_eq!(Some(("proxy.local".to_owned(), 3128)), c.proxy);
+    }
+
+    #[test]
+    fn test_precedence() {
+        let explicit = Settings { venue: Some("ARGEX".to_owned()), ..Settings::default() };
+        let env = Settings {
+            profile: Some("live".to_owned()),
+            venue: Some("ENVEX".to_owned()),
+            base_url: Some("http://localhost:9000".to_owned()),
+            ..Settings::default()
+        };
+        let c = Config::resolve(explicit, env, Some(FILE)).unwrap();
+        assert_eq!("live", c.profile);
+        assert_eq!("live-key", c.api_key.expose());
+        assert_eq!("http://localhost:9000", c.base_url);
+        assert_eq!(Some("ARGEX".to_owned()), c.venue);
+        assert_eq!(None, c.read_timeout);
+        assert_eq!(ApiProfile::default(), c.api);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_inline_key_permissions() {
+        use std::env;
+        use std::fs::{self, File};
+        use std::io::Write;
+        use std::os::unix::fs::PermissionsExt;
+        let path = env::temp_dir().join(format!("michromer-config-test-{}", ::std::process::id()));
+        assert_eq!(None, read_config(&path).unwrap());
+        File::create(&path).unwrap().write_all(FILE.as_bytes()).unwrap();
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(read_config(&path).is_err());
+        File::create(&path).unwrap().write_all(b"profile = \"sim\"").unwrap();
+        assert!(read_config(&path).unwrap().is_some());
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        File::create(&path).unwrap().write_all(FILE.as_bytes()).unwrap();
+        let res = read_config(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(res.unwrap().is_some());
+        // There, but not a file.
+        assert!(read_config(&env::temp_dir()).is_err());
+    }
+
+    #[test]
+    fn test_key_file_read_last() {
+        // The file's key file doesn't exist, but the explicit key wins, so
+        // it's never read.
+        let file = "api_key_file = \"/nonexistent/michromer-key\"\nbase_url = \"http://x\"";
+        let explicit = Settings { api_key: Some(Secret::from("k")), ..Settings::default() };
+        let c = Config::resolve(explicit, Settings::default(), Some(file)).unwrap();
+        assert_eq!("k", c.api_key.expose());
+        assert!(Config::resolve(Settings::default(), Settings::default(), Some(file)).is_err());
+    }
+
+    #[test]
+    fn test_missing_key() {
+        assert!(Config::resolve(Settings::default(), Settings::default(), None).is_err());
+        let missing = Settings {
+            api_key_file: Some(PathBuf::from("/nonexistent/michromer-key")),
+            ..Settings::default()
+        };
+        assert!(Config::resolve(Settings::default(), missing, Some(FILE)).is_err());
+        assert!(Config::resolve(Settings::default(),
+                                Settings::default(),
+                                Some("read_timeout_ms = \"soon\""))
+            .is_err());
+        assert!(Config::resolve(Settings::default(),
+                                Settings::default(),
+                                Some("profile = \"sim\"\nproxy = \"nowhere\""))
+            .is_err());
+    }
+}
@@ -0,0 +1,286 @@
+use backtest::MarketEvent;
+use client::LevelClient;
+use data::{BidAsk, Level, OrderbookResponse, QuoteResponse};
+use error::Result;
+use http::HttpClient;
+use std::cmp::Reverse;
+use types::{Price, Qty, Symbol, Venue};
+
+/// One price level on one venue.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VenueLevel {
+    pub venue: Venue,
+    pub price: Price,
+    pub qty: Qty,
+}
+
+/// The best bid and offer across every venue.
+///
+/// Sizes add up everything shown at the best price, on every venue
+/// showing it.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Nbbo {
+    pub bid: Option<Price>,
+    pub bid_size: Qty,
+    pub bid_venues: Vec<Venue>,
+    pub ask: Option<Price>,
+    pub ask_size: Qty,
+    pub ask_venues: Vec<Venue>,
+}
+
+impl Nbbo {
+    /// Ask minus bid in cents, negative when the market is crossed.
+    pub fn spread(&self) -> Option<i64> {
+        match (self.bid, self.ask) {
+            (Some(b), Some(a)) => Some(a.0 as i64 - b.0 as i64),
+            _ => None,
+        }
+    }
+
+    /// Some venue is bidding above what another is offering at.
+    pub fn is_crossed(&self) -> bool {
+        self.spread().is_some_and(|s| s < 0)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct VenueBook {
+    bids: Vec<BidAsk>,
+    asks: Vec<BidAsk>,
+}
+
+/// One symbol's books on every venue of a level, merged.
+///
+/// Feed it order books or quotes, or have it `poll` the venues. Each update
+/// hands back the new NBBO when it changed, so callers only react to moves.
+#[derive(Debug, Clone)]
+pub struct ConsolidatedBook {
+    symbol: Symbol,
+    books: Vec<(Venue, VenueBook)>,
+    nbbo: Nbbo,
+}
+
+impl ConsolidatedBook {
+    pub fn new(symbol: Symbol, venues: &[Venue]) -> ConsolidatedBook {
+        ConsolidatedBook {
+            symbol: symbol,
+            books: venues.iter().map(|v| (v.clone(), VenueBook::default())).collect(),
+            nbbo: Nbbo::default(),
+        }
+    }
+
+    /// A book covering every venue in a level.
+    pub fn for_level(level: &Level, symbol: Symbol) -> ConsolidatedBook {
+        ConsolidatedBook::new(symbol, &level.venues)
+    }
+
+    pub fn symbol(&self) -> &Symbol {
+        &self.symbol
+    }
+
+    pub fn venues(&self) -> Vec<&Venue> {
+        self.books.iter().map(|b| &b.0).collect()
+    }
+
+    pub fn nbbo(&self) -> &Nbbo {
+        &self.nbbo
+    }
+
+    /// Replace a venue's depth with a fresh order book.
+    ///
+    /// Books for other symbols are ignored. A venue that wasn't known yet
+    /// is added.
+    pub fn update_orderbook(&mut self, book: &OrderbookResponse) -> Option<Nbbo> {
+        if book.symbol != self.symbol {
+            return None;
+        }
+        {
+            let vb = self.book_mut(&book.venue);
+            vb.bids = book.bids.clone().unwrap_or_default();
+            vb.asks = book.asks.clone().unwrap_or_default();
+        }
+        self.refresh()
+    }
+
+    /// Replace a venue's depth with the top of book from a quote.
+    ///
+    /// Quotes don't carry depth, so the venue is left with at most one
+    /// level a side until its next order book.
+    pub fn update_quote(&mut self, quote: &QuoteResponse) -> Option<Nbbo> {
+        if quote.symbol != self.symbol {
+            return None;
+        }
+        let top = |price: Option<Price>, qty: Qty, is_buy: bool| {
+            price.into_iter()
+                .filter(|_| !qty.is_zero())
+                .map(|p| {
+                    BidAsk {
+                        price: p,
+                        qty: qty,
+                        is_buy: is_buy,
+                    }
+                })
+                .collect()
+        };
+        {
+            let vb = self.book_mut(&quote.venue);
+            vb.bids = top(quote.bid, quote.bid_size, true);
+            vb.asks = top(quote.ask, quote.ask_size, false);
+        }
+        self.refresh()
+    }
+
+    /// Apply a quote or order book.
+    pub fn update(&mut self, event: &MarketEvent) -> Option<Nbbo> {
+        match *event {
+            MarketEvent::Quote(ref q) => self.update_quote(q),
+            MarketEvent::Orderbook(ref b) => self.update_orderbook(b),
+        }
+    }
+
+    /// Fetch the order book from every venue, then report the NBBO if it
+    /// moved.
+    pub fn poll<T: HttpClient + Clone>(&mut self, client: &LevelClient<T>) -> Result<Option<Nbbo>> {
+        let before = self.nbbo.clone();
+        let venues: Vec<Venue> = self.books.iter().map(|b| b.0.clone()).collect();
+        for venue in venues {
+            let book = try!(client.orderbook(&venue, &self.symbol));
+            self.update_orderbook(&book);
+        }
+        if self.nbbo != before {
+            Ok(Some(self.nbbo.clone()))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Every venue's bids, best first. Venues tied on price keep the order
+    /// they were given in.
+    pub fn bids(&self) -> Vec<VenueLevel> {
+        let mut levels = self.levels(|b| &b.bids);
+        levels.sort_by_key(|l| Reverse(l.price));
+        levels
+    }
+
+    /// Every venue's asks, best first.
+    pub fn asks(&self) -> Vec<VenueLevel> {
+        let mut levels = self.levels(|b| &b.asks);
+        levels.sort_by_key(|l| l.price);
+        levels
+    }
+
+    fn levels<F: Fn(&VenueBook) -> &Vec<BidAsk>>(&self, side: F) -> Vec<VenueLevel> {
+        self.books
+            .iter()
+            .flat_map(|b| {
+                side(&b.1).iter().map(move |l| {
+                    VenueLevel {
+                        venue: b.0.clone(),
+                        price: l.price,
+                        qty: l.qty,
+                    }
+                })
+            })
+            .collect()
+    }
+
+    fn book_mut(&mut self, venue: &Venue) -> &mut VenueBook {
+        match self.books.iter().position(|b| b.0 == *venue) {
+            Some(i) => &mut self.books[i].1,
+            None => {
+                self.books.push((venue.clone(), VenueBook::default()));
+                &mut self.books.last_mut().unwrap().1
+            }
+        }
+    }
+
+    /// Recompute the NBBO, handing it back if it changed.
+    fn refresh(&mut self) -> Option<Nbbo> {
+        let (bid, bid_size, bid_venues) = best(&self.bids());
+        let (ask, ask_size, ask_venues) = best(&self.asks());
+        let nbbo = Nbbo {
+            bid: bid,
+            bid_size: bid_size,
+            bid_venues: bid_venues,
+            ask: ask,
+            ask_size: ask_size,
+            ask_venues: ask_venues,
+        };
+        if nbbo == self.nbbo {
+            None
+        } else {
+            self.nbbo = nbbo.clone();
+            Some(nbbo)
+        }
+    }
+}
+
+/// The best price on a sorted side, how much is there and where.
+fn best(levels: &[VenueLevel]) -> (Option<Price>, Qty, Vec<Venue>) {
+    let price = match levels.first() {
+        Some(l) => l.price,
+        None => return (None, Qty(0), Vec::new()),
+    };
+    let mut size = Qty(0);
+    let mut venues: Vec<Venue> = Vec::new();
+    for l in levels.iter().take_while(|l| l.price == price) {
+        size += l.qty;
+        if !venues.contains(&l.venue) {
+            venues.push(l.venue.clone());
+        }
+    }
+    (Some(price), size, venues)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_support::{book, level};
+    use types::{Price, Qty};
+
+    #[test]
+    fn test_nbbo_and_ladder() {
+        let mut c = ConsolidatedBook::new("FOOBAR".into(), &["AEX".into(), "BEX".into()]);
+        let first = c.update_orderbook(&book("AEX",
+                                             vec![level(5000, 10, true), level(4990, 20, true)],
+                                             vec![level(5020, 10, false)]))
+            .unwrap();
+        assert_eq!(Some(Price(5000)), first.bid);
+        assert_eq!(Some(20), first.spread());
+
+        let second = c.update_orderbook(&book("BEX",
+                                              vec![level(5000, 5, true)],
+                                              vec![level(5010, 7, false), level(5020, 3, false)]))
+            .unwrap();
+        assert_eq!(Qty(15), second.bid_size);
+        assert_eq!(vec![Venue::from("AEX"), Venue::from("BEX")], second.bid_venues);
+        assert_eq!(Some(Price(5010)), second.ask);
+        assert_eq!(vec![Venue::from("BEX")], second.ask_venues);
+        assert!(!second.is_crossed());
+
+        let asks = c.asks();
+        assert_eq!(3, asks.len());
+        assert_eq!(("BEX", 5010), (asks[0].venue.as_str(), asks[0].price.0));
+        assert_eq!(("AEX", 5020), (asks[1].venue.as_str(), asks[1].price.0));
+        assert_eq!(("BEX", 5020), (asks[2].venue.as_str(), asks[2].price.0));
+        assert_eq!(Price(4990), c.bids()[2].price);
+
+        // Same book again doesn't move anything, and other symbols are ignored.
+        assert!(c.update_orderbook(&book("BEX",
+                                         vec![level(5000, 5, true)],
+                                         vec![level(5010, 7, false), level(5020, 3, false)]))
+            .is_none());
+        let mut other = book("AEX", vec![], vec![]);
+        other.symbol = "OTHER".into();
+        assert!(c.update_orderbook(&other).is_none());
+    }
+
+    #[test]
+    fn test_crossed() {
+        let mut c = ConsolidatedBook::new("FOOBAR".into(), &["AEX".into(), "BEX".into()]);
+        c.update_orderbook(&book("AEX", vec![level(5050, 10, true)], vec![]));
+        let n = c.update_orderbook(&book("BEX", vec![], vec![level(5010, 10, false)])).unwrap();
+        assert!(n.is_crossed());
+        assert_eq!(Some(-40), n.spread());
+    }
+}
@@ -5,16 +5,41 @@ use std::collections::HashMap;
 use chrono::*;
 use std::fmt;
 use serde;
+use types::{Account, OrderId, Price, Qty, Symbol, Venue};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Level {
     pub ok: bool,
     #[serde(rename="instanceId")]
     pub instance_id: i64,
-    pub account: String,
+    pub account: Account,
     pub instructions: HashMap<String, String>,
-    pub tickers: Vec<String>,
-    pub venues: Vec<String>,
+    pub tickers: Vec<Symbol>,
+    pub venues: Vec<Venue>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InstanceStatusResponse {
+    pub ok: bool,
+    pub done: bool,
+    pub id: i64,
+    pub state: String,
+    pub details: Option<InstanceDetails>,
+    pub flash: Option<HashMap<String, String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InstanceDetails {
+    #[serde(rename="endOfTheWorldDay")]
+    pub end_of_the_world_day: u64,
+    #[serde(rename="tradingDay")]
+    pub trading_day: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StopLevelResponse {
+    pub ok: bool,
+    pub error: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -26,7 +51,7 @@ pub struct HeartBeatResponse {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct VenueHeartBeatResponse {
     pub ok: bool,
-    pub venue: String,
+    pub venue: Venue,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -38,23 +63,23 @@ pub struct StockListResponse {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct StockSymbol {
     pub name: String,
-    pub symbol: String,
+    pub symbol: Symbol,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct OrderbookResponse {
     pub ok: bool,
-    pub venue: String,
-    pub symbol: String,
+    pub venue: Venue,
+    pub symbol: Symbol,
     pub bids: Option<Vec<BidAsk>>,
     pub asks: Option<Vec<BidAsk>>,
-    pub ts: String,
+    pub ts: DateTime<UTC>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct BidAsk {
-    pub price: u64,
-    pub qty: u64,
+    pub price: Price,
+    pub qty: Qty,
     #[serde(rename="isBuy")]
     pub is_buy: bool,
 }
@@ -62,21 +87,21 @@ pub struct BidAsk {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct QuoteResponse {
     pub ok: bool,
-    pub symbol: String,
-    pub venue: String,
-    pub bid: Option<u64>,
-    pub ask: Option<u64>,
+    pub symbol: Symbol,
+    pub venue: Venue,
+    pub bid: Option<Price>,
+    pub ask: Option<Price>,
     #[serde(rename="bidSize")]
-    pub bid_size: u64,
+    pub bid_size: Qty,
     #[serde(rename="askSize")]
-    pub ask_size: u64,
+    pub ask_size: Qty,
     #[serde(rename="bidDepth")]
-    pub bid_depth: u64,
+    pub bid_depth: Qty,
     #[serde(rename="askDepth")]
-    pub ask_depth: u64,
-    pub last: Option<u64>,
+    pub ask_depth: Qty,
+    pub last: Option<Price>,
     #[serde(rename="lastSize")]
-    pub last_size: Option<u64>,
+    pub last_size: Option<Qty>,
     #[serde(rename="lastTrade")]
     pub last_trade: Option<DateTime<UTC>>,
     #[serde(rename="quoteTime")]
@@ -197,11 +222,11 @@ impl serde::Deserialize for OrderType {
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Order {
-    pub account: String,
-    pub venue: String,
-    pub stock: String,
-    pub price: u64,
-    pub qty: u64,
+    pub account: Account,
+    pub venue: Venue,
+    pub stock: Symbol,
+    pub price: Price,
+    pub qty: Qty,
     pub direction: OrderDirection,
     #[serde(rename="orderType")]
     pub order_type: OrderType,
@@ -210,31 +235,62 @@ pub struct Order {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct OrderResponse {
     pub ok: bool,
-    pub symbol: String,
-    pub venue: String,
+    pub symbol: Symbol,
+    pub venue: Venue,
     pub direction: Option<OrderDirection>,
     #[serde(rename="originalQty")]
-    pub original_qty: u64,
-    pub qty: u64,
-    pub price: u64,
+    pub original_qty: Qty,
+    pub qty: Qty,
+    pub price: Price,
     #[serde(rename="orderType")]
     pub order_type: OrderType,
-    pub id: u64,
-    pub account: String,
+    pub id: OrderId,
+    pub account: Account,
     pub ts: DateTime<UTC>,
     pub fills: Vec<Fill>,
     #[serde(rename="totalFilled")]
-    pub total_filled: u64,
+    pub total_filled: Qty,
     pub open: bool,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AccountOrdersResponse {
+    pub ok: bool,
+    pub venue: Venue,
+    pub orders: Vec<OrderResponse>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Fill {
-    pub price: u64,
-    pub qty: u64,
+    pub price: Price,
+    pub qty: Qty,
     pub ts: DateTime<UTC>,
 }
 
+/// One fill on an account's execution feed, from
+/// `ApiProfile::executions_url`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Execution {
+    pub ok: bool,
+    pub account: Account,
+    pub venue: Venue,
+    pub symbol: Symbol,
+    /// The account's order that traded, as it stands after the fill.
+    pub order: OrderResponse,
+    #[serde(rename="standingId")]
+    pub standing_id: OrderId,
+    #[serde(rename="incomingId")]
+    pub incoming_id: OrderId,
+    pub price: Price,
+    pub filled: Qty,
+    #[serde(rename="filledAt")]
+    pub filled_at: DateTime<UTC>,
+    #[serde(rename="standingComplete")]
+    pub standing_complete: bool,
+    #[serde(rename="incomingComplete")]
+    pub incoming_complete: bool,
+}
+
 pub fn parse_response<T: Deserialize>(buf: &str) -> Result<T, Error> {
     let l: T = try!(serde_json::from_str(&buf));
     Ok(l)
@@ -245,15 +301,16 @@ mod tests {
     use super::*;
     use serde_json;
     use std::collections::HashMap;
+    use types::{OrderId, Price, Qty};
 
     #[test]
     fn test_encode_order() {
         let o = Order {
-            account: "tstacc".to_string(),
-            venue: "testven".to_string(),
-            stock: "BOOK".to_string(),
-            price: 1000,
-            qty: 5000,
+            account: "tstacc".into(),
+            venue: "testven".into(),
+            stock: "BOOK".into(),
+            price: Price(1000),
+            qty: Qty(5000),
             direction: OrderDirection::Buy,
             order_type: OrderType::ImmediateOrCancel,
         };
@@ -266,7 +323,7 @@ mod tests {
         let l = Level {
             ok: true,
             instance_id: 998,
-            account: "tacc".to_string(),
+            account: "tacc".into(),
             instructions: HashMap::new(),
             tickers: vec![],
             venues: vec![],
@@ -278,8 +335,8 @@ mod tests {
     #[test]
     fn test_bid_ask_encode_field_name() {
         let ba = BidAsk {
-            price: 1009,
-            qty: 100,
+            price: Price(1009),
+            qty: Qty(100),
             is_buy: true,
         };
         let res_string = serde_json::to_string(&ba).unwrap();
@@ -290,16 +347,16 @@ mod tests {
     fn test_quote_response_fieled_name() {
         let qr = QuoteResponse {
             ok: true,
-            symbol: "BOOK".to_string(),
-            venue: "MYEX".to_string(),
-            bid: Some(101),
-            ask: Some(105),
-            bid_size: 50,
-            ask_size: 1,
-            bid_depth: 9,
-            ask_depth: 9,
-            last: Some(102),
-            last_size: Some(3),
+            symbol: "BOOK".into(),
+            venue: "MYEX".into(),
+            bid: Some(Price(101)),
+            ask: Some(Price(105)),
+            bid_size: Qty(50),
+            ask_size: Qty(1),
+            bid_depth: Qty(9),
+            ask_depth: Qty(9),
+            last: Some(Price(102)),
+            last_size: Some(Qty(3)),
             last_trade: Some("2015-12-04T09:02:16.680986205Z".parse().unwrap()),
             quote_time: Some("2015-12-04T09:02:16.680986205Z".parse().unwrap()),
         };
@@ -319,17 +376,17 @@ mod tests {
         let or = OrderResponse {
             ok: true,
             direction: None,
-            symbol: "BOOK".to_string(),
-            venue: "MYEX".to_string(),
-            original_qty: 10,
-            qty: 9,
-            price: 1045,
+            symbol: "BOOK".into(),
+            venue: "MYEX".into(),
+            original_qty: Qty(10),
+            qty: Qty(9),
+            price: Price(1045),
             order_type: OrderType::Limit,
-            id: 99803,
-            account: "MYACC".to_string(),
+            id: OrderId(99803),
+            account: "MYACC".into(),
             ts: "2015-12-04T09:02:16.680986205Z".parse().unwrap(),
             fills: vec![],
-            total_filled: 1,
+            total_filled: Qty(1),
             open: true,
         };
         let res_string = serde_json::to_string(&or).unwrap();
@@ -359,5 +416,50 @@ mod tests {
 
         let o: OrderResponse = parse_response(&o_json).unwrap();
         assert!(o.ok);
+        assert_eq!(Price(26382757), o.price);
+        assert_eq!(OrderId(2138), o.id);
+        assert_eq!(Qty(5000), o.fills[0].qty);
+    }
+
+    #[test]
+    fn test_decode_orderbook() {
+        let b_json = "{\"ok\": true, \"venue\": \"TESTEX\", \"symbol\": \"FOOBAR\", \"bids\": \
+                      [{\"price\": 5000, \"qty\": 10, \"isBuy\": true}], \"asks\": null, \
+                      \"ts\": \"2016-06-02T16:20:53.024542Z\"}";
+
+        let b: OrderbookResponse = parse_response(&b_json).unwrap();
+        let ts: DateTime<UTC> = "2016-06-02T16:20:53.024542Z".parse().unwrap();
+        assert_eq!(ts, b.ts);
+        assert_eq!(Qty(10), b.bids.unwrap()[0].qty);
+    }
+
+    #[test]
+    fn test_decode_execution() {
+        let e_json = "{\"ok\": true, \"account\": \"TB123\", \"venue\": \"TESTEX\", \
+                      \"symbol\": \"FOOBAR\", \"order\": {\"ok\": true, \"symbol\": \
+                      \"FOOBAR\", \"venue\": \"TESTEX\", \"direction\": \"sell\", \
+                      \"originalQty\": 20, \"qty\": 0, \"price\": 5000, \"orderType\": \
+                      \"limit\", \"id\": 7, \"account\": \"TB123\", \"ts\": \
+                      \"2016-06-02T16:20:53.024542Z\", \"fills\": [], \"totalFilled\": 20, \
+                      \"open\": false}, \"standingId\": 7, \"incomingId\": 8, \"price\": \
+                      5000, \"filled\": 20, \"filledAt\": \"2016-06-02T16:20:53.024563Z\", \
+                      \"standingComplete\": true, \"incomingComplete\": false}";
+
+        let e: Execution = parse_response(&e_json).unwrap();
+        assert_eq!("TB123", e.account.as_str());
+        assert_eq!(OrderId(8), e.incoming_id);
+        assert_eq!(Qty(20), e.filled);
+        assert!(e.standing_complete);
+    }
+
+    #[test]
+    fn test_decode_instance_status() {
+        let s_json = "{\"ok\": true, \"done\": false, \"id\": 1090, \"state\": \"open\", \
+                      \"details\": {\"endOfTheWorldDay\": 540, \"tradingDay\": 12}, \
+                      \"flash\": {\"info\": \"Level started.\"}}";
+
+        let s: InstanceStatusResponse = parse_response(&s_json).unwrap();
+        assert_eq!(12, s.details.unwrap().trading_day);
+        assert_eq!("Level started.", s.flash.unwrap()["info"]);
     }
 }
@@ -0,0 +1,475 @@
+use error::{Error, Result};
+use percent_encoding::{PATH_SEGMENT_ENCODE_SET, percent_decode, utf8_percent_encode};
+use std::fmt;
+use std::str::FromStr;
+use types::{Account, OrderId, Symbol, Venue};
+
+/// Every REST path the Stockfighter api serves. The paths shown are where
+/// stockfighter.io serves them; see `ApiProfile` for servers laid out
+/// differently.
+///
+/// `path` builds the path with each name percent-encoded, and parsing a
+/// path gives back the same endpoint, so anything routing requests agrees
+/// with the client on what each path means.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Endpoint {
+    /// `/ob/api/heartbeat`
+    Heartbeat,
+    /// `/ob/api/venues/:venue/heartbeat`
+    VenueHeartbeat { venue: Venue },
+    /// `/ob/api/venues/:venue/stocks`
+    Stocks { venue: Venue },
+    /// `/ob/api/venues/:venue/stocks/:stock`
+    Orderbook { venue: Venue, stock: Symbol },
+    /// `/ob/api/venues/:venue/stocks/:stock/quote`
+    Quote { venue: Venue, stock: Symbol },
+    /// `/ob/api/venues/:venue/stocks/:stock/orders`, where orders are placed.
+    Orders { venue: Venue, stock: Symbol },
+    /// `/ob/api/venues/:venue/stocks/:stock/orders/:id`, for checking on
+    /// and canceling an order.
+    Order {
+        venue: Venue,
+        stock: Symbol,
+        id: OrderId,
+    },
+    /// `/ob/api/venues/:venue/accounts/:account/orders`
+    AccountOrders { venue: Venue, account: Account },
+    /// `/ob/api/venues/:venue/accounts/:account/stocks/:stock/orders`
+    AccountStockOrders {
+        venue: Venue,
+        account: Account,
+        stock: Symbol,
+    },
+    /// `/gm/levels/:level`
+    StartLevel { level: String },
+    /// `/gm/instances/:instance`
+    InstanceStatus { instance: i64 },
+    /// `/gm/instances/:instance/stop`
+    StopLevel { instance: i64 },
+    /// `/gm/instances/:instance/restart`
+    RestartLevel { instance: i64 },
+    /// `/gm/instances/:instance/resume`
+    ResumeLevel { instance: i64 },
+}
+
+/// Which half of the api an endpoint lives under.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Service {
+    OrderBook,
+    GameMaster,
+}
+
+/// Where a Stockfighter compatible server puts things.
+///
+/// The defaults match stockfighter.io. Clones that serve the same api
+/// under other prefixes, or want the key in another header, only need a
+/// different profile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiProfile {
+    /// Prefix for the venue and order endpoints, `/ob/api` on stockfighter.io.
+    pub order_book_prefix: String,
+    /// Prefix for the level endpoints, `/gm` on stockfighter.io.
+    pub game_master_prefix: String,
+    /// Header the api key is sent in.
+    pub auth_header: String,
+    /// Full url the tickertape and executions websockets live under.
+    pub ws_base: String,
+}
+
+impl Default for ApiProfile {
+    fn default() -> ApiProfile {
+        ApiProfile {
+            order_book_prefix: "/ob/api".to_owned(),
+            game_master_prefix: "/gm".to_owned(),
+            auth_header: "X-Starfighter-Authorization".to_owned(),
+            ws_base: "wss://api.stockfighter.io/ob/api/ws".to_owned(),
+        }
+    }
+}
+
+impl ApiProfile {
+    /// Websocket url for quotes on a venue, or just one stock on it.
+    pub fn tickertape_url(&self, account: &str, venue: &str, stock: Option<&str>) -> String {
+        self.ws_url(account, venue, "tickertape", stock)
+    }
+
+    /// Websocket url for an account's fills on a venue, or just one stock on it.
+    pub fn executions_url(&self, account: &str, venue: &str, stock: Option<&str>) -> String {
+        self.ws_url(account, venue, "executions", stock)
+    }
+
+    fn ws_url(&self, account: &str, venue: &str, feed: &str, stock: Option<&str>) -> String {
+        let mut url = self.ws_base.trim_end_matches('/').to_owned() +
+                      &join(&[account, "venues", venue, feed]);
+        if let Some(stock) = stock {
+            url.push_str(&join(&["stocks", stock]));
+        }
+        url
+    }
+
+    fn prefix(&self, service: Service) -> &str {
+        let prefix = match service {
+            Service::OrderBook => &self.order_book_prefix,
+            Service::GameMaster => &self.game_master_prefix,
+        };
+        prefix.trim_end_matches('/')
+    }
+}
+
+impl Endpoint {
+    /// A fixed name for the endpoint, without any of the names or ids in
+    /// its path.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Endpoint::Heartbeat => "heartbeat",
+            Endpoint::VenueHeartbeat { .. } => "venue_heartbeat",
+            Endpoint::Stocks { .. } => "stocks",
+            Endpoint::Orderbook { .. } => "orderbook",
+            Endpoint::Quote { .. } => "quote",
+            Endpoint::Orders { .. } => "orders",
+            Endpoint::Order { .. } => "order",
+            Endpoint::AccountOrders { .. } => "account_orders",
+            Endpoint::AccountStockOrders { .. } => "account_stock_orders",
+            Endpoint::StartLevel { .. } => "start_level",
+            Endpoint::InstanceStatus { .. } => "instance_status",
+            Endpoint::StopLevel { .. } => "stop_level",
+            Endpoint::RestartLevel { .. } => "restart_level",
+            Endpoint::ResumeLevel { .. } => "resume_level",
+        }
+    }
+
+    /// The venue in the path, if there is one.
+    pub fn venue(&self) -> Option<&Venue> {
+        match *self {
+            Endpoint::VenueHeartbeat { ref venue } |
+            Endpoint::Stocks { ref venue } |
+            Endpoint::Orderbook { ref venue, .. } |
+            Endpoint::Quote { ref venue, .. } |
+            Endpoint::Orders { ref venue, .. } |
+            Endpoint::Order { ref venue, .. } |
+            Endpoint::AccountOrders { ref venue, .. } |
+            Endpoint::AccountStockOrders { ref venue, .. } => Some(venue),
+            _ => None,
+        }
+    }
+
+    /// The stock in the path, if there is one.
+    pub fn stock(&self) -> Option<&Symbol> {
+        match *self {
+            Endpoint::Orderbook { ref stock, .. } |
+            Endpoint::Quote { ref stock, .. } |
+            Endpoint::Orders { ref stock, .. } |
+            Endpoint::Order { ref stock, .. } |
+            Endpoint::AccountStockOrders { ref stock, .. } => Some(stock),
+            _ => None,
+        }
+    }
+
+    /// The order id in the path, if there is one.
+    pub fn order_id(&self) -> Option<OrderId> {
+        match *self {
+            Endpoint::Order { id, .. } => Some(id),
+            _ => None,
+        }
+    }
+
+    /// The path for this endpoint on stockfighter.io, starting with `/`.
+    pub fn path(&self) -> String {
+        self.path_in(&ApiProfile::default())
+    }
+
+    /// The path for this endpoint on a server laid out like `api`.
+    pub fn path_in(&self, api: &ApiProfile) -> String {
+        let (service, segments) = self.segments();
+        let segments: Vec<&str> = segments.iter().map(|s| s.as_str()).collect();
+        api.prefix(service).to_owned() + &join(&segments)
+    }
+
+    /// The unescaped path segments after the service's prefix.
+    fn segments(&self) -> (Service, Vec<String>) {
+        let ob = |s: &[&str]| (Service::OrderBook, s.iter().map(|s| s.to_string()).collect());
+        let gm = |s: &[&str]| (Service::GameMaster, s.iter().map(|s| s.to_string()).collect());
+        match *self {
+            Endpoint::Heartbeat => ob(&["heartbeat"]),
+            Endpoint::VenueHeartbeat { ref venue } => ob(&["venues", venue, "heartbeat"]),
+            Endpoint::Stocks { ref venue } => ob(&["venues", venue, "stocks"]),
+            Endpoint::Orderbook { ref venue, ref stock } => ob(&["venues", venue, "stocks", stock]),
+            Endpoint::Quote { ref venue, ref stock } => {
+                ob(&["venues", venue, "stocks", stock, "quote"])
+            }
+            Endpoint::Orders { ref venue, ref stock } => {
+                ob(&["venues", venue, "stocks", stock, "orders"])
+            }
+            Endpoint::Order { ref venue, ref stock, id } => {
+                ob(&["venues", venue, "stocks", stock, "orders", &id.to_string()])
+            }
+            Endpoint::AccountOrders { ref venue, ref account } => {
+                ob(&["venues", venue, "accounts", account, "orders"])
+            }
+            Endpoint::AccountStockOrders { ref venue, ref account, ref stock } => {
+                ob(&["venues", venue, "accounts", account, "stocks", stock, "orders"])
+            }
+            Endpoint::StartLevel { ref level } => gm(&["levels", level]),
+            Endpoint::InstanceStatus { instance } => gm(&["instances", &instance.to_string()]),
+            Endpoint::StopLevel { instance } => gm(&["instances", &instance.to_string(), "stop"]),
+            Endpoint::RestartLevel { instance } => {
+                gm(&["instances", &instance.to_string(), "restart"])
+            }
+            Endpoint::ResumeLevel { instance } => {
+                gm(&["instances", &instance.to_string(), "resume"])
+            }
+        }
+    }
+
+    /// Work out which stockfighter.io endpoint a request path is for.
+    ///
+    /// Any query string and trailing `/` are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Errors out when:
+    ///  the path isn't one the api serves
+    ///  a segment isn't valid percent-encoded utf-8
+    ///  an order or instance id isn't a number
+    pub fn parse(path: &str) -> Result<Endpoint> {
+        Endpoint::parse_in(path, &ApiProfile::default())
+    }
+
+    /// Work out which endpoint a request path is for on a server laid out
+    /// like `api`.
+    pub fn parse_in(path: &str, api: &ApiProfile) -> Result<Endpoint> {
+        let path_only = path.split('?').next().unwrap_or("");
+        for &service in &[Service::OrderBook, Service::GameMaster] {
+            let segments = match under(path_only, api.prefix(service)).and_then(decode) {
+                Some(s) => s,
+                None => continue,
+            };
+            let s: Vec<&str> = segments.iter().map(|s| s.as_str()).collect();
+            let endpoint = match service {
+                Service::OrderBook => order_book(&s),
+                Service::GameMaster => game_master(&s),
+            };
+            if let Some(e) = endpoint {
+                return Ok(e);
+            }
+        }
+        Err(Error::Parse(format!("{:?} isn't an api path", path)))
+    }
+}
+
+/// The rest of `path` if it's `prefix` or something below it.
+fn under<'a>(path: &'a str, prefix: &str) -> Option<&'a str> {
+    if !path.starts_with(prefix) {
+        return None;
+    }
+    let rest = &path[prefix.len()..];
+    if rest.is_empty() || rest.starts_with('/') {
+        Some(rest)
+    } else {
+        None
+    }
+}
+
+/// Split a path into percent-decoded segments.
+fn decode(path: &str) -> Option<Vec<String>> {
+    path.trim_matches('/')
+        .split('/')
+        .map(|s| percent_decode(s.as_bytes()).decode_utf8().ok().map(|d| d.into_owned()))
+        .collect()
+}
+
+fn order_book(s: &[&str]) -> Option<Endpoint> {
+    let endpoint = match *s {
+        ["heartbeat"] => Endpoint::Heartbeat,
+        ["venues", venue, "heartbeat"] => Endpoint::VenueHeartbeat { venue: venue.into() },
+        ["venues", venue, "stocks"] => Endpoint::Stocks { venue: venue.into() },
+        ["venues", venue, "stocks", stock] => {
+            Endpoint::Orderbook {
+                venue: venue.into(),
+                stock: stock.into(),
+            }
+        }
+        ["venues", venue, "stocks", stock, "quote"] => {
+            Endpoint::Quote {
+                venue: venue.into(),
+                stock: stock.into(),
+            }
+        }
+        ["venues", venue, "stocks", stock, "orders"] => {
+            Endpoint::Orders {
+                venue: venue.into(),
+                stock: stock.into(),
+            }
+        }
+        ["venues", venue, "stocks", stock, "orders", id] => {
+            Endpoint::Order {
+                venue: venue.into(),
+                stock: stock.into(),
+                id: match id.parse() {
+                    Ok(id) => id,
+                    Err(_) => return None,
+                },
+            }
+        }
+        ["venues", venue, "accounts", account, "orders"] => {
+            Endpoint::AccountOrders {
+                venue: venue.into(),
+                account: account.into(),
+            }
+        }
+        ["venues", venue, "accounts", account, "stocks", stock, "orders"] => {
+            Endpoint::AccountStockOrders {
+                venue: venue.into(),
+                account: account.into(),
+                stock: stock.into(),
+            }
+        }
+        _ => return None,
+    };
+    Some(endpoint)
+}
+
+fn game_master(s: &[&str]) -> Option<Endpoint> {
+    match *s {
+        ["levels", level] => Some(Endpoint::StartLevel { level: level.to_owned() }),
+        ["instances", id] => id.parse().ok().map(|i| Endpoint::InstanceStatus { instance: i }),
+        ["instances", id, "stop"] => id.parse().ok().map(|i| Endpoint::StopLevel { instance: i }),
+        ["instances", id, "restart"] => {
+            id.parse().ok().map(|i| Endpoint::RestartLevel { instance: i })
+        }
+        ["instances", id, "resume"] => {
+            id.parse().ok().map(|i| Endpoint::ResumeLevel { instance: i })
+        }
+        _ => None,
+    }
+}
+
+/// Percent-encode each segment and join them into an absolute path.
+fn join(segments: &[&str]) -> String {
+    let mut path = String::new();
+    for s in segments {
+        path.push('/');
+        path.extend(utf8_percent_encode(s, PATH_SEGMENT_ENCODE_SET));
+    }
+    path
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.path())
+    }
+}
+
+impl FromStr for Endpoint {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Endpoint> {
+        Endpoint::parse(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::OrderId;
+
+    #[test]
+    fn test_round_trip() {
+        let endpoints = vec![Endpoint::Heartbeat,
+                             Endpoint::VenueHeartbeat { venue: "TESTEX".into() },
+                             Endpoint::Stocks { venue: "TESTEX".into() },
+                             Endpoint::Orderbook {
+                                 venue: "TESTEX".into(),
+                                 stock: "FOOBAR".into(),
+                             },
+                             Endpoint::Quote {
+                                 venue: "TESTEX".into(),
+                                 stock: "FOOBAR".into(),
+                             },
+                             Endpoint::Orders {
+                                 venue: "TESTEX".into(),
+                                 stock: "FOOBAR".into(),
+                             },
+                             Endpoint::Order {
+                                 venue: "TESTEX".into(),
+                                 stock: "FOOBAR".into(),
+                                 id: OrderId(1234),
+                             },
+                             Endpoint::AccountOrders {
+                                 venue: "TESTEX".into(),
+                                 account: "EXB123456".into(),
+                             },
+                             Endpoint::AccountStockOrders {
+                                 venue: "TESTEX".into(),
+                                 account: "EXB123456".into(),
+                                 stock: "FOOBAR".into(),
+                             },
+                             Endpoint::StartLevel { level: "first_steps".to_owned() },
+                             Endpoint::InstanceStatus { instance: 1090 },
+                             Endpoint::StopLevel { instance: 1090 },
+                             Endpoint::RestartLevel { instance: 1090 },
+                             Endpoint::ResumeLevel { instance: 1090 }];
+        for e in endpoints {
+            assert_eq!(e, Endpoint::parse(&e.path()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_path() {
+        let e = Endpoint::Order {
+            venue: "TESTEX".into(),
+            stock: "FOOBAR".into(),
+            id: OrderId(1234),
+        };
+        assert_eq!("/ob/api/venues/TESTEX/stocks/FOOBAR/orders/1234", e.path());
+        assert_eq!(e,
+                   "/ob/api/venues/TESTEX/stocks/FOOBAR/orders/1234/?x=1".parse().unwrap());
+    }
+
+    #[test]
+    fn test_odd_names_escaped() {
+        let e = Endpoint::Quote {
+            venue: "TEST EX".into(),
+            stock: "FOO/BAR?".into(),
+        };
+        assert_eq!("/ob/api/venues/TEST%20EX/stocks/FOO%2FBAR%3F/quote", e.path());
+        assert_eq!(e, Endpoint::parse(&e.path()).unwrap());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Endpoint::parse("/ob/api/nope").is_err());
+        assert!(Endpoint::parse("/ob/api/venues/TESTEX/stocks/FOOBAR/orders/abc").is_err());
+        assert!(Endpoint::parse("/gm/instances/abc/stop").is_err());
+        assert!(Endpoint::parse("").is_err());
+        assert!(Endpoint::parse("/ob/apix/heartbeat").is_err());
+    }
+
+    #[test]
+    fn test_other_profile() {
+        let api = ApiProfile {
+            order_book_prefix: "/api/".to_owned(),
+            game_master_prefix: "".to_owned(),
+            ..ApiProfile::default()
+        };
+        let quote = Endpoint::Quote {
+            venue: "TESTEX".into(),
+            stock: "FOOBAR".into(),
+        };
+        assert_eq!("/api/venues/TESTEX/stocks/FOOBAR/quote", quote.path_in(&api));
+        assert_eq!(quote, Endpoint::parse_in(&quote.path_in(&api), &api).unwrap());
+        let stop = Endpoint::StopLevel { instance: 1090 };
+        assert_eq!("/instances/1090/stop", stop.path_in(&api));
+        assert_eq!(stop, Endpoint::parse_in("/instances/1090/stop", &api).unwrap());
+        assert!(Endpoint::parse_in(&stop.path(), &api).is_err());
+    }
+
+    #[test]
+    fn test_ws_urls() {
+        let api = ApiProfile::default();
+        assert_eq!("wss://api.stockfighter.io/ob/api/ws/EXB123456/venues/TESTEX/tickertape",
+                   api.tickertape_url("EXB123456", "TESTEX", None));
+        assert_eq!("wss://api.stockfighter.io/ob/api/ws/EXB123456/venues/TESTEX/executions/\
+                    stocks/FOOBAR",
+                   api.executions_url("EXB123456", "TESTEX", Some("FOOBAR")));
+    }
+}
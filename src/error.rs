@@ -2,7 +2,9 @@ use std::io::Error as IOError;
 use std::error::Error as StdError;
 use hyper::error::Error as HyperError;
 use serde_json::Error as SerdeJsonError;
+use toml::de::Error as TomlError;
 use std::fmt;
+use order::OrderError;
 
 
 #[derive(Debug)]
@@ -10,6 +12,10 @@ pub enum Error {
     Hyper(HyperError),
     IO(IOError),
     JSON(SerdeJsonError),
+    TOML(TomlError),
+    Config(String),
+    Parse(String),
+    Order(OrderError),
 }
 
 impl From<IOError> for Error {
@@ -27,6 +33,30 @@ impl From<SerdeJsonError> for Error {
         Error::JSON(e)
     }
 }
+impl From<TomlError> for Error {
+    fn from(e: TomlError) -> Error {
+        Error::TOML(e)
+    }
+}
+impl From<OrderError> for Error {
+    fn from(e: OrderError) -> Error {
+        Error::Order(e)
+    }
+}
+impl Error {
+    /// Short name of the variant, for counting errors by kind.
+    pub fn kind(&self) -> &'static str {
+        match *self {
+            Error::Hyper(_) => "hyper",
+            Error::IO(_) => "io",
+            Error::JSON(_) => "json",
+            Error::TOML(_) => "toml",
+            Error::Config(_) => "config",
+            Error::Parse(_) => "parse",
+            Error::Order(_) => "order",
+        }
+    }
+}
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "Michromer Error: {}", self.description())
@@ -38,6 +68,10 @@ impl StdError for Error {
             Error::Hyper(ref e) => e.description(),
             Error::IO(ref e) => e.description(),
             Error::JSON(ref e) => e.description(),
+            Error::TOML(ref e) => e.description(),
+            Error::Config(ref s) => s,
+            Error::Parse(ref s) => s,
+            Error::Order(ref e) => e.description(),
         }
     }
 
@@ -46,6 +80,9 @@ impl StdError for Error {
             Error::Hyper(ref e) => Some(e),
             Error::IO(ref e) => Some(e),
             Error::JSON(ref e) => Some(e),
+            Error::TOML(ref e) => Some(e),
+            Error::Order(ref e) => Some(e),
+            Error::Config(_) | Error::Parse(_) => None,
         }
     }
 }
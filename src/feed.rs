@@ -0,0 +1,293 @@
+//! Watch quote feeds for signs a venue has gone wrong.
+//!
+//! A venue that freezes keeps answering with the same quote, so polling
+//! alone won't notice. `FeedMonitor` follows each venue and symbol's
+//! `quote_time` and `last_trade` and raises a `FeedEvent` when the quotes
+//! stop moving, when time runs backwards, or when the bid meets or crosses
+//! the ask.
+
+use chrono::{DateTime, Duration, UTC};
+use data::QuoteResponse;
+use std::collections::BTreeMap;
+use types::{Price, Symbol, Venue};
+
+/// Something wrong, or right again, with one venue's quotes for a symbol.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FeedEvent {
+    /// `quote_time` hasn't moved for at least the monitor's `stale_after`.
+    Stale {
+        venue: Venue,
+        symbol: Symbol,
+        silent: Duration,
+    },
+    /// A timestamp went back in time. `field` is `quote_time` or
+    /// `last_trade`.
+    Backwards {
+        venue: Venue,
+        symbol: Symbol,
+        field: &'static str,
+        from: DateTime<UTC>,
+        to: DateTime<UTC>,
+    },
+    /// The bid is above the ask.
+    Crossed {
+        venue: Venue,
+        symbol: Symbol,
+        bid: Price,
+        ask: Price,
+    },
+    /// The bid and ask are the same price.
+    Locked {
+        venue: Venue,
+        symbol: Symbol,
+        price: Price,
+    },
+    /// A feed that was stale, crossed or locked is fine again.
+    Recovered { venue: Venue, symbol: Symbol },
+}
+
+/// What's known about one venue's quotes for a symbol.
+#[derive(Debug, Clone)]
+pub struct FeedHealth {
+    /// The latest server times seen.
+    pub quote_time: Option<DateTime<UTC>>,
+    pub last_trade: Option<DateTime<UTC>>,
+    /// When, on our clock, `quote_time` last moved forward.
+    pub last_progress: DateTime<UTC>,
+    pub stale: bool,
+    pub crossed: bool,
+    pub locked: bool,
+    /// How many times either timestamp has gone backwards.
+    pub backwards: u64,
+}
+
+impl FeedHealth {
+    pub fn is_healthy(&self) -> bool {
+        !(self.stale || self.crossed || self.locked)
+    }
+}
+
+/// Tracks the health of every venue and symbol it's shown quotes for.
+///
+/// A quiet book looks just like a frozen one, since the venue only bumps
+/// `quote_time` when something changes, so `stale_after` should be longer
+/// than the level normally goes without trading.
+#[derive(Debug, Clone)]
+pub struct FeedMonitor {
+    stale_after: Duration,
+    feeds: BTreeMap<(Venue, Symbol), FeedHealth>,
+}
+
+impl FeedMonitor {
+    pub fn new(stale_after: Duration) -> FeedMonitor {
+        FeedMonitor {
+            stale_after: stale_after,
+            feeds: BTreeMap::new(),
+        }
+    }
+
+    /// Check a new quote, received just now.
+    pub fn on_quote(&mut self, quote: &QuoteResponse) -> Vec<FeedEvent> {
+        self.on_quote_at(quote, UTC::now())
+    }
+
+    /// Check a new quote, received at local time `now`.
+    pub fn on_quote_at(&mut self, quote: &QuoteResponse, now: DateTime<UTC>) -> Vec<FeedEvent> {
+        let key = (quote.venue.clone(), quote.symbol.clone());
+        let mut events = Vec::new();
+        let stale_after = self.stale_after;
+        let feed = self.feeds.entry(key.clone()).or_insert_with(|| {
+            FeedHealth {
+                quote_time: None,
+                last_trade: None,
+                last_progress: now,
+                stale: false,
+                crossed: false,
+                locked: false,
+                backwards: 0,
+            }
+        });
+        let was_healthy = feed.is_healthy();
+        let backwards = |events: &mut Vec<FeedEvent>, field, from, to| {
+            events.push(FeedEvent::Backwards {
+                venue: key.0.clone(),
+                symbol: key.1.clone(),
+                field: field,
+                from: from,
+                to: to,
+            })
+        };
+
+        if let Some(t) = quote.quote_time {
+            match feed.quote_time {
+                Some(last) if t < last => {
+                    feed.backwards += 1;
+                    backwards(&mut events, "quote_time", last, t);
+                }
+                Some(last) if t == last => {}
+                _ => {
+                    feed.quote_time = Some(t);
+                    feed.last_progress = now;
+                    feed.stale = false;
+                }
+            }
+        }
+        if let Some(t) = quote.last_trade {
+            match feed.last_trade {
+                Some(last) if t < last => {
+                    feed.backwards += 1;
+                    backwards(&mut events, "last_trade", last, t);
+                }
+                _ => feed.last_trade = Some(t),
+            }
+        }
+
+        let (crossed, locked) = match (quote.bid, quote.ask) {
+            (Some(b), Some(a)) => (b > a, b == a),
+            _ => (false, false),
+        };
+        if crossed && !feed.crossed {
+            events.push(FeedEvent::Crossed {
+                venue: key.0.clone(),
+                symbol: key.1.clone(),
+                bid: quote.bid.unwrap_or_default(),
+                ask: quote.ask.unwrap_or_default(),
+            });
+        }
+        if locked && !feed.locked {
+            events.push(FeedEvent::Locked {
+                venue: key.0.clone(),
+                symbol: key.1.clone(),
+                price: quote.bid.unwrap_or_default(),
+            });
+        }
+        feed.crossed = crossed;
+        feed.locked = locked;
+
+        if let Some(e) = check(&key, feed, stale_after, now) {
+            events.push(e);
+        }
+        if !was_healthy && feed.is_healthy() {
+            events.push(FeedEvent::Recovered {
+                venue: key.0.clone(),
+                symbol: key.1.clone(),
+            });
+        }
+        events
+    }
+
+    /// Look for feeds that have gone stale with nothing new coming in.
+    /// Call this now and then, since a feed that has stopped altogether
+    /// won't bring any quotes to `on_quote`.
+    pub fn check(&mut self) -> Vec<FeedEvent> {
+        self.check_at(UTC::now())
+    }
+
+    pub fn check_at(&mut self, now: DateTime<UTC>) -> Vec<FeedEvent> {
+        let stale_after = self.stale_after;
+        self.feeds
+            .iter_mut()
+            .filter_map(|(key, feed)| check(key, feed, stale_after, now))
+            .collect()
+    }
+
+    pub fn health(&self, venue: &str, symbol: &str) -> Option<&FeedHealth> {
+        self.feeds.get(&(venue.into(), symbol.into()))
+    }
+
+    /// Whether a feed is fit to trade on. Feeds that haven't been seen
+    /// aren't.
+    pub fn is_healthy(&self, venue: &str, symbol: &str) -> bool {
+        self.health(venue, symbol).is_some_and(|h| h.is_healthy())
+    }
+
+    /// Every feed that's stale, crossed or locked.
+    pub fn unhealthy(&self) -> Vec<&(Venue, Symbol)> {
+        self.feeds.iter().filter(|&(_, h)| !h.is_healthy()).map(|(k, _)| k).collect()
+    }
+}
+
+/// Mark a feed stale once it's gone quiet for long enough.
+fn check(key: &(Venue, Symbol),
+         feed: &mut FeedHealth,
+         stale_after: Duration,
+         now: DateTime<UTC>)
+         -> Option<FeedEvent> {
+    let silent = now.signed_duration_since(feed.last_progress);
+    if feed.stale || silent < stale_after {
+        return None;
+    }
+    feed.stale = true;
+    Some(FeedEvent::Stale {
+        venue: key.0.clone(),
+        symbol: key.1.clone(),
+        silent: silent,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Duration, UTC};
+    use data::QuoteResponse;
+    use types::{Price, Qty};
+
+    fn at(millis: i64) -> DateTime<UTC> {
+        let start: DateTime<UTC> = "2016-06-02T16:20:00Z".parse().unwrap();
+        start + Duration::milliseconds(millis)
+    }
+
+    fn quote(bid: u64, ask: u64, quote_time: i64, last_trade: i64) -> QuoteResponse {
+        QuoteResponse {
+            ok: true,
+            symbol: "FOOBAR".into(),
+            venue: "TESTEX".into(),
+            bid: Some(Price(bid)),
+            ask: Some(Price(ask)),
+            bid_size: Qty(10),
+            ask_size: Qty(10),
+            bid_depth: Qty(10),
+            ask_depth: Qty(10),
+            last: Some(Price(bid)),
+            last_size: Some(Qty(1)),
+            last_trade: Some(at(last_trade)),
+            quote_time: Some(at(quote_time)),
+        }
+    }
+
+    #[test]
+    fn test_stale_and_recovered() {
+        let mut m = FeedMonitor::new(Duration::milliseconds(500));
+        assert!(!m.is_healthy("TESTEX", "FOOBAR"));
+        assert!(m.on_quote_at(&quote(100, 101, 0, 0), at(0)).is_empty());
+        assert!(m.is_healthy("TESTEX", "FOOBAR"));
+        // The same quote again and again.
+        assert!(m.on_quote_at(&quote(100, 101, 0, 0), at(300)).is_empty());
+        let events = m.on_quote_at(&quote(100, 101, 0, 0), at(600));
+        assert!(matches!(events[..], [FeedEvent::Stale { silent, .. }]
+                         if silent == Duration::milliseconds(600)));
+        assert!(m.check_at(at(900)).is_empty());
+        assert_eq!(1, m.unhealthy().len());
+        let events = m.on_quote_at(&quote(100, 101, 950, 0), at(1000));
+        assert!(matches!(events[..], [FeedEvent::Recovered { .. }]));
+        assert!(matches!(m.check_at(at(1600))[..], [FeedEvent::Stale { .. }]));
+    }
+
+    #[test]
+    fn test_backwards_and_crossed() {
+        let mut m = FeedMonitor::new(Duration::seconds(5));
+        m.on_quote_at(&quote(100, 101, 10, 10), at(0));
+        let events = m.on_quote_at(&quote(101, 100, 5, 8), at(1));
+        assert_eq!(3, events.len());
+        assert!(matches!(events[0], FeedEvent::Backwards { field: "quote_time", .. }));
+        assert!(matches!(events[1], FeedEvent::Backwards { field: "last_trade", .. }));
+        assert!(matches!(events[2], FeedEvent::Crossed { bid: Price(101), ask: Price(100), .. }));
+        assert_eq!(2, m.health("TESTEX", "FOOBAR").unwrap().backwards);
+        // Still crossed: nothing new to say.
+        assert!(m.on_quote_at(&quote(101, 100, 20, 20), at(2)).is_empty());
+        let events = m.on_quote_at(&quote(100, 100, 30, 20), at(3));
+        assert!(matches!(events[..], [FeedEvent::Locked { price: Price(100), .. }]));
+        let events = m.on_quote_at(&quote(99, 100, 40, 20), at(4));
+        assert!(matches!(events[..], [FeedEvent::Recovered { .. }]));
+    }
+}
@@ -0,0 +1,272 @@
+//! Read the numbers out of the game master's flash messages.
+//!
+//! Instance status comes with a `flash` of prose like "You've purchased
+//! 4,000 shares at an average cost of $87.20. ... Our target price is
+//! $82.05." `parse` turns that into `LevelHint`s so bots don't each need
+//! their own string matching.
+
+use data::InstanceStatusResponse;
+use types::{Price, Qty};
+
+/// Something the game master told us about how a level is going.
+///
+/// Amounts of money are in cents and can be negative.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub enum LevelHint {
+    /// The price to beat, e.g. the average cost to buy below.
+    TargetPrice(Price),
+    /// Shares bought or sold so far.
+    SharesFilled(Qty),
+    /// The average price paid or received for those shares.
+    AverageCost(Price),
+    Cash(i64),
+    /// Shares held, negative when short.
+    Position(i64),
+    /// Net asset value.
+    Nav(i64),
+    /// The value of the position held.
+    Exposure(i64),
+    /// A flash of kind `warning`, `danger` or `error`.
+    Warning(String),
+}
+
+/// Pull every hint out of one flash message.
+pub fn parse(text: &str) -> Vec<LevelHint> {
+    let mut hints = Vec::new();
+    if let Some(p) = after(text, "target price").and_then(price) {
+        hints.push(LevelHint::TargetPrice(p));
+    }
+    if let Some(n) = shares(text) {
+        hints.push(LevelHint::SharesFilled(n));
+    }
+    let average = after(text, "average cost").or_else(|| after(text, "average price"));
+    if let Some(p) = average.and_then(price) {
+        hints.push(LevelHint::AverageCost(p));
+    }
+    if let Some(c) = after(text, "cash").and_then(money) {
+        hints.push(LevelHint::Cash(c));
+    }
+    if let Some(n) = after(text, "position").and_then(number) {
+        hints.push(LevelHint::Position(n));
+    }
+    if let Some(v) = after(text, "nav").and_then(money) {
+        hints.push(LevelHint::Nav(v));
+    }
+    if let Some(v) = after(text, "exposure").and_then(money) {
+        hints.push(LevelHint::Exposure(v));
+    }
+    hints
+}
+
+/// Every hint in an instance's flash, warnings included.
+pub fn hints(status: &InstanceStatusResponse) -> Vec<LevelHint> {
+    let mut hints = Vec::new();
+    if let Some(ref flash) = status.flash {
+        let mut kinds: Vec<&String> = flash.keys().collect();
+        kinds.sort();
+        for kind in kinds {
+            let text = &flash[kind];
+            if kind == "warning" || kind == "danger" || kind == "error" {
+                hints.push(LevelHint::Warning(text.clone()));
+            }
+            hints.extend(parse(text));
+        }
+    }
+    hints
+}
+
+/// Remembers what the game master has already said, so each hint is only
+/// handed on once, or again when its value changes.
+#[derive(Debug, Clone, Default)]
+pub struct FlashWatcher {
+    seen: Vec<LevelHint>,
+}
+
+impl FlashWatcher {
+    pub fn new() -> FlashWatcher {
+        FlashWatcher::default()
+    }
+
+    /// The hints in this status that weren't in the last one.
+    pub fn update(&mut self, status: &InstanceStatusResponse) -> Vec<LevelHint> {
+        let current = hints(status);
+        let fresh = current.iter().filter(|h| !self.seen.contains(h)).cloned().collect();
+        self.seen = current;
+        fresh
+    }
+
+    /// The latest value of every hint.
+    pub fn latest(&self) -> &[LevelHint] {
+        &self.seen
+    }
+}
+
+/// Whatever follows the first case-insensitive match of `label`.
+fn after<'a>(text: &'a str, label: &str) -> Option<&'a str> {
+    let lower = text.to_ascii_lowercase();
+    let mut from = 0;
+    while let Some(i) = lower[from..].find(label) {
+        let start = from + i;
+        let end = start + label.len();
+        // Only whole words, so "nav" doesn't match inside "canvas".
+        let before_ok = start == 0 || !lower.as_bytes()[start - 1].is_ascii_alphabetic();
+        let after_ok = end == lower.len() || !lower.as_bytes()[end].is_ascii_alphabetic();
+        if before_ok && after_ok {
+            return Some(&text[end..]);
+        }
+        from = end;
+    }
+    None
+}
+
+/// A dollar amount near the start of `text`, as cents. Filler like
+/// ": ", "is" or "of" before it is skipped, commas are allowed, and
+/// the sign can be either side of the `$`.
+fn money(text: &str) -> Option<i64> {
+    let rest = skip_filler(text);
+    let (negative, rest) = sign(rest);
+    if !rest.starts_with('$') {
+        return None;
+    }
+    let (negative2, rest) = sign(rest[1..].trim_start());
+    let (dollars, rest) = match digits(rest) {
+        (Some(d), rest) => (d, rest),
+        (None, _) => return None,
+    };
+    let cents: String = match rest.strip_prefix('.') {
+        Some(frac) => frac.chars().take_while(|c| c.is_ascii_digit()).take(2).collect(),
+        None => String::new(),
+    };
+    let cents = match cents.len() {
+        0 => 0,
+        1 => cents.parse::<i64>().unwrap_or(0) * 10,
+        _ => cents.parse::<i64>().unwrap_or(0),
+    };
+    // A long enough run of digits won't fit in cents.
+    let value = whole(dollars).and_then(|d| d.checked_mul(100)).and_then(|d| d.checked_add(cents));
+    let value = match value {
+        Some(v) => v,
+        None => return None,
+    };
+    Some(if negative || negative2 { -value } else { value })
+}
+
+fn price(text: &str) -> Option<Price> {
+    money(text).and_then(|c| if c >= 0 { Some(Price(c as u64)) } else { None })
+}
+
+/// A plain whole number near the start of `text`.
+fn number(text: &str) -> Option<i64> {
+    let rest = skip_filler(text);
+    let (negative, rest) = sign(rest);
+    let (n, _) = digits(rest);
+    n.and_then(whole).map(|n| if negative { -n } else { n })
+}
+
+fn whole(n: u64) -> Option<i64> {
+    if n > i64::MAX as u64 { None } else { Some(n as i64) }
+}
+
+/// The number just before the word "shares".
+fn shares(text: &str) -> Option<Qty> {
+    let lower = text.to_ascii_lowercase();
+    let i = match lower.find(" shares") {
+        Some(i) => i,
+        None => return None,
+    };
+    let before = &text[..i];
+    // Just past the last character that isn't part of the number, which
+    // can be more than one byte long, like a non-breaking space.
+    let start = before.char_indices()
+        .rev()
+        .find(|&(_, c)| !(c.is_ascii_digit() || c == ','))
+        .map_or(0, |(i, c)| i + c.len_utf8());
+    digits(&before[start..]).0.map(Qty)
+}
+
+fn skip_filler(text: &str) -> &str {
+    let mut rest = text.trim_start_matches(|c: char| c.is_whitespace() || c == ':' || c == '=');
+    for word in &["is ", "of ", "was "] {
+        if rest.starts_with(word) {
+            rest = rest[word.len()..].trim_start();
+        }
+    }
+    rest
+}
+
+fn sign(text: &str) -> (bool, &str) {
+    match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    }
+}
+
+/// Leading digits, with commas between them ignored.
+fn digits(text: &str) -> (Option<u64>, &str) {
+    let end = text.find(|c: char| !(c.is_ascii_digit() || c == ',')).unwrap_or(text.len());
+    let found: String = text[..end].chars().filter(|c| c.is_ascii_digit()).collect();
+    (found.parse().ok(), &text[end..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data::InstanceStatusResponse;
+    use std::collections::HashMap;
+    use types::{Price, Qty};
+
+    #[test]
+    fn test_chock_a_block() {
+        let text = "You've purchased 4,123 shares at an average cost of $99.03. Our target \
+                    price is $92.73. Anything below that wins.";
+        assert_eq!(vec![LevelHint::TargetPrice(Price(9273)),
+                        LevelHint::SharesFilled(Qty(4123)),
+                        LevelHint::AverageCost(Price(9903))],
+                   parse(text));
+        assert_eq!(vec![LevelHint::TargetPrice(Price(4210))],
+                   parse("The target price is $ 42.1"));
+    }
+
+    #[test]
+    fn test_nav() {
+        let text = "Cash: $-1,234.56 Position: -120 NAV: $5,000.00";
+        assert_eq!(vec![LevelHint::Cash(-123456),
+                        LevelHint::Position(-120),
+                        LevelHint::Nav(500000)],
+                   parse(text));
+        assert!(parse("a canvas with no numbers").is_empty());
+    }
+
+    #[test]
+    fn test_odd_input() {
+        assert_eq!(vec![LevelHint::SharesFilled(Qty(4000))],
+                   parse("You've purchased\u{a0}4,000 shares"));
+        assert_eq!(vec![LevelHint::Position(5)],
+                   parse("Cash: $99999999999999999999 Position: 5"));
+        assert_eq!(vec![LevelHint::Position(5)],
+                   parse("Cash: $9,999,999,999,999,999,999 Position: 5"));
+    }
+
+    #[test]
+    fn test_watcher() {
+        let mut flash = HashMap::new();
+        flash.insert("info".to_owned(), "NAV: $10.00".to_owned());
+        flash.insert("warning".to_owned(), "Exposure is -$2,000".to_owned());
+        let mut status = InstanceStatusResponse {
+            ok: true,
+            done: false,
+            id: 1,
+            state: "open".to_owned(),
+            details: None,
+            flash: Some(flash),
+        };
+        let mut w = FlashWatcher::new();
+        assert_eq!(vec![LevelHint::Nav(1000),
+                        LevelHint::Warning("Exposure is -$2,000".to_owned()),
+                        LevelHint::Exposure(-200000)],
+                   w.update(&status));
+        assert!(w.update(&status).is_empty());
+        status.flash.as_mut().unwrap().insert("info".to_owned(), "NAV: $12.00".to_owned());
+        assert_eq!(vec![LevelHint::Nav(1200)], w.update(&status));
+    }
+}
@@ -1,76 +1,298 @@
-
-use hyper::header::Headers;
-use hyper::client;
+use hyper::client::{self, ProxyConfig};
+use hyper::client::pool::{self, Pool};
+use hyper::header::{Headers, UserAgent};
+use hyper::net::HttpsConnector;
+use std::path::PathBuf;
 use std::sync::Arc;
-use error::Result;
+use error::{Error, Result};
+use secret::Secret;
 use std::io::Read;
+use std::time::Duration;
+use transport::{TimeoutConnector, TlsClient};
 
-/// Header class used to send the api key on each http request.
-header! { (XStarfighterAuthorization, "X-Starfighter-Authorization") => [String] }
+/// The header stockfighter.io reads the api key from.
+pub static DEFAULT_AUTH_HEADER: &'static str = "X-Starfighter-Authorization";
 
 #[derive(Debug, Clone)]
 pub struct AuthHttpClient {
-    api_key: String,
+    api_key: Secret,
+    auth_header: String,
+    http_client: PlainHttpClient,
+}
+
+/// Sends requests without an api key, as the transport under a middleware
+/// `Stack` with an `Auth` layer.
+#[derive(Debug, Clone)]
+pub struct PlainHttpClient {
+    user_agent: Option<String>,
     http_client: Arc<client::Client>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Method {
+    Get,
+    Post,
+    Delete,
+}
+
+impl Method {
+    /// Whether sending the request twice is the same as sending it once.
+    pub fn is_idempotent(&self) -> bool {
+        *self != Method::Post
+    }
+}
+
 pub trait HttpClient {
     fn get(&self, url: &str) -> Result<String>;
     fn post(&self, url: &str, body: Option<&str>) -> Result<String>;
     fn delete(&self, url: &str) -> Result<String>;
+
+    /// Send a request with extra headers, such as the one an `Auth` layer
+    /// adds. Clients that only implement `get`, `post` and `delete` can't
+    /// set headers, so they refuse requests that have any rather than
+    /// quietly sending them without, api key and all.
+    fn send(&self,
+            method: Method,
+            url: &str,
+            body: Option<&str>,
+            headers: &[(String, Secret)])
+            -> Result<String> {
+        if let Some((name, _)) = headers.first() {
+            return Err(Error::Config(format!("this http client can't send the {} header", name)));
+        }
+        match method {
+            Method::Get => self.get(url),
+            Method::Post => self.post(url, body),
+            Method::Delete => self.delete(url),
+        }
+    }
+}
+
+/// Sets up the connections an `AuthHttpClient` makes. Made by
+/// `AuthHttpClient::builder`.
+#[derive(Debug, Clone)]
+pub struct AuthHttpClientBuilder {
+    api_key: Secret,
+    auth_header: String,
+    connect_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    pool_size: usize,
+    root_certificates: Vec<PathBuf>,
+    proxy: Option<(String, u16)>,
+    user_agent: Option<String>,
+}
+
+impl AuthHttpClientBuilder {
+    pub fn api_key(mut self, key: &str) -> AuthHttpClientBuilder {
+        self.api_key = Secret::from(key);
+        self
+    }
+
+    /// Send the api key in a different header, for servers that don't use
+    /// `X-Starfighter-Authorization`.
+    pub fn auth_header(mut self, name: &str) -> AuthHttpClientBuilder {
+        self.auth_header = name.to_owned();
+        self
+    }
+
+    /// Give up on connecting after this long.
+    pub fn connect_timeout(mut self, timeout: Option<Duration>) -> AuthHttpClientBuilder {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Give up on a response that hasn't arrived after this long.
+    pub fn read_timeout(mut self, timeout: Option<Duration>) -> AuthHttpClientBuilder {
+        self.read_timeout = timeout;
+        self
+    }
+
+    /// Give up on sending a request after this long.
+    pub fn write_timeout(mut self, timeout: Option<Duration>) -> AuthHttpClientBuilder {
+        self.write_timeout = timeout;
+        self
+    }
+
+    /// How many idle connections to keep alive for each host. Defaults to 5.
+    pub fn pool_size(mut self, size: usize) -> AuthHttpClientBuilder {
+        self.pool_size = size;
+        self
+    }
+
+    /// Also trust the certificates in a PEM file, e.g. for a local server
+    /// with a self-signed certificate.
+    pub fn add_root_certificate<P: Into<PathBuf>>(mut self, pem: P) -> AuthHttpClientBuilder {
+        self.root_certificates.push(pem.into());
+        self
+    }
+
+    /// Send every request through an http proxy. `https` urls are tunneled
+    /// through it with `CONNECT`.
+    pub fn http_proxy(mut self, host: &str, port: u16) -> AuthHttpClientBuilder {
+        self.proxy = Some((host.to_owned(), port));
+        self
+    }
+
+    pub fn user_agent(mut self, agent: &str) -> AuthHttpClientBuilder {
+        self.user_agent = Some(agent.to_owned());
+        self
+    }
+
+    /// # Errors
+    ///
+    /// Errors out when:
+    ///  a root certificate file can't be loaded
+    pub fn build(self) -> Result<AuthHttpClient> {
+        let api_key = self.api_key.clone();
+        let auth_header = self.auth_header.clone();
+        Ok(AuthHttpClient {
+            api_key: api_key,
+            auth_header: auth_header,
+            http_client: try!(self.build_plain()),
+        })
+    }
+
+    /// Build a client that doesn't send the api key, for a middleware
+    /// `Stack` to add it with an `Auth` layer.
+    ///
+    /// # Errors
+    ///
+    /// Errors out when:
+    ///  a root certificate file can't be loaded
+    pub fn build_plain(self) -> Result<PlainHttpClient> {
+        let tls = try!(TlsClient::new(&self.root_certificates));
+        let connector = TimeoutConnector { connect_timeout: self.connect_timeout };
+        let pool_config = pool::Config { max_idle: self.pool_size };
+        let mut http_client = match self.proxy {
+            Some((host, port)) => {
+                let mut proxy = ProxyConfig::new("http", host, port, connector, tls);
+                proxy.set_pool_config(Some(pool_config));
+                client::Client::with_proxy_config(proxy)
+            }
+            None => {
+                let https = HttpsConnector::with_connector(tls, connector);
+                client::Client::with_connector(Pool::with_connector(pool_config, https))
+            }
+        };
+        http_client.set_read_timeout(self.read_timeout);
+        http_client.set_write_timeout(self.write_timeout);
+        Ok(PlainHttpClient {
+            user_agent: self.user_agent,
+            http_client: Arc::new(http_client),
+        })
+    }
 }
 
 impl AuthHttpClient {
     pub fn new(key: &str) -> AuthHttpClient {
-        AuthHttpClient {
-            api_key: key.to_string(),
-            http_client: Arc::new(client::Client::new()),
-        }
+        AuthHttpClient::new_with_timeouts(key, None, None)
     }
-    fn build_post<'a>(&'a self, url: &str, body: Option<&'a str>) -> client::RequestBuilder {
-        let mut build = self.http_client.post(url);
-        if let Some(b) = body {
-            build = build.body(b);
+    /// Construct a client that gives up on reads or writes that take longer
+    /// than the given timeouts.
+    pub fn new_with_timeouts(key: &str,
+                             read_timeout: Option<Duration>,
+                             write_timeout: Option<Duration>)
+                             -> AuthHttpClient {
+        AuthHttpClient::builder()
+            .api_key(key)
+            .read_timeout(read_timeout)
+            .write_timeout(write_timeout)
+            .build()
+            .expect("a client without extra root certificates always builds")
+    }
+    /// Start setting up a client with more control over its connections.
+    pub fn builder() -> AuthHttpClientBuilder {
+        AuthHttpClientBuilder {
+            api_key: Secret::from(""),
+            auth_header: DEFAULT_AUTH_HEADER.to_owned(),
+            connect_timeout: None,
+            read_timeout: None,
+            write_timeout: None,
+            pool_size: pool::Config::default().max_idle,
+            root_certificates: Vec::new(),
+            proxy: None,
+            user_agent: None,
         }
-        build = self.add_header(build);
-        build
     }
-    fn build_get(&self, url: &str) -> client::RequestBuilder {
-        let mut build = self.http_client.get(url);
-        build = self.add_header(build);
-        build
+    /// Send the api key in a different header, for servers that don't use
+    /// `X-Starfighter-Authorization`.
+    pub fn set_auth_header(&mut self, name: &str) {
+        self.auth_header = name.to_owned();
+    }
+}
+
+impl HttpClient for AuthHttpClient {
+    fn get(&self, url: &str) -> Result<String> {
+        self.send(Method::Get, url, None, &[])
+    }
+    fn post(&self, url: &str, body: Option<&str>) -> Result<String> {
+        self.send(Method::Post, url, body, &[])
     }
-    fn build_delete(&self, url: &str) -> client::RequestBuilder {
-        let mut build = self.http_client.delete(url);
-        build = self.add_header(build);
-        build
+    fn delete(&self, url: &str) -> Result<String> {
+        self.send(Method::Delete, url, None, &[])
     }
-    fn add_header<'a>(&'a self, b: client::RequestBuilder<'a>) -> client::RequestBuilder {
-        let mut headers = Headers::new();
-        headers.set(XStarfighterAuthorization(self.api_key.clone()));
-        b.headers(headers)
+    fn send(&self,
+            method: Method,
+            url: &str,
+            body: Option<&str>,
+            headers: &[(String, Secret)])
+            -> Result<String> {
+        let mut all = vec![(self.auth_header.clone(), self.api_key.clone())];
+        all.extend(headers.iter().cloned());
+        self.http_client.send(method, url, body, &all)
     }
 }
-impl HttpClient for AuthHttpClient {
+
+impl PlainHttpClient {
+    pub fn new() -> PlainHttpClient {
+        AuthHttpClient::builder()
+            .build_plain()
+            .expect("a client without extra root certificates always builds")
+    }
+}
+
+impl Default for PlainHttpClient {
+    fn default() -> PlainHttpClient {
+        PlainHttpClient::new()
+    }
+}
+
+impl HttpClient for PlainHttpClient {
     fn get(&self, url: &str) -> Result<String> {
-        let mut r = try!(self.build_get(url).send());
-        let mut buf = String::new();
-        try!(r.read_to_string(&mut buf));
-        trace!("get buf = {:?}", buf);
-        Ok(buf)
+        self.send(Method::Get, url, None, &[])
     }
     fn post(&self, url: &str, body: Option<&str>) -> Result<String> {
-        let mut r = try!(self.build_post(url, body).send());
-        let mut buf = String::new();
-        try!(r.read_to_string(&mut buf));
-        trace!("post buf = {:?}", buf);
-        Ok(buf)
+        self.send(Method::Post, url, body, &[])
     }
     fn delete(&self, url: &str) -> Result<String> {
-        let mut r = try!(self.build_delete(url).send());
+        self.send(Method::Delete, url, None, &[])
+    }
+    fn send(&self,
+            method: Method,
+            url: &str,
+            body: Option<&str>,
+            headers: &[(String, Secret)])
+            -> Result<String> {
+        let mut build = match method {
+            Method::Get => self.http_client.get(url),
+            Method::Post => self.http_client.post(url),
+            Method::Delete => self.http_client.delete(url),
+        };
+        if let Some(b) = body {
+            build = build.body(b);
+        }
+        let mut h = Headers::new();
+        for (name, value) in headers {
+            h.set_raw(name.clone(), vec![value.expose().as_bytes().to_vec()]);
+        }
+        if let Some(ref agent) = self.user_agent {
+            h.set(UserAgent(agent.to_owned()));
+        }
+        let mut r = try!(build.headers(h).send());
         let mut buf = String::new();
         try!(r.read_to_string(&mut buf));
-        trace!("del buf = {:?}", buf);
+        trace!(body = %buf, "response");
         Ok(buf)
     }
 }
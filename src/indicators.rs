@@ -0,0 +1,570 @@
+//! Indicators that update one value at a time.
+//!
+//! Each update is O(1) on average: windowed indicators keep running totals,
+//! and only go back over the window once every `len` updates to clear out
+//! rounding error. A strategy holds the indicators it wants and
+//! feeds them from its hooks, and they can be fed bars from a
+//! `bars::BarBuilder` just the same.
+//!
+//! Prices are in cents, as `f64`.
+
+use bars::Bar;
+use chrono::{DateTime, UTC};
+use data::{BidAsk, OrderbookResponse, QuoteResponse};
+use std::collections::VecDeque;
+use types::{Price, Qty};
+
+/// An indicator over a series of prices.
+pub trait Indicator {
+    type Output;
+
+    /// Add the next price.
+    fn update(&mut self, price: f64);
+
+    /// The latest value, once there's been enough to go on.
+    fn value(&self) -> Option<Self::Output>;
+
+    /// Add a bar's close.
+    fn on_bar(&mut self, bar: &Bar) {
+        self.update(bar.close.0 as f64);
+    }
+
+    /// Add a quote's midpoint. One-sided quotes are skipped.
+    fn on_quote(&mut self, quote: &QuoteResponse) {
+        if let Some(m) = mid(quote) {
+            self.update(m);
+        }
+    }
+}
+
+/// Halfway between the bid and the ask.
+pub fn mid(quote: &QuoteResponse) -> Option<f64> {
+    match (quote.bid, quote.ask) {
+        (Some(b), Some(a)) => Some((b.0 + a.0) as f64 / 2.0),
+        _ => None,
+    }
+}
+
+/// The last `len` values with their mean and sum of squared deviations.
+///
+/// Updated Welford's way as values come and go, and worked out afresh
+/// from the values every `len` pushes, so rounding can't build up over a
+/// long session.
+#[derive(Debug, Clone)]
+struct Window {
+    len: usize,
+    values: VecDeque<f64>,
+    mean: f64,
+    m2: f64,
+    pushes: usize,
+}
+
+impl Window {
+    fn new(len: usize) -> Window {
+        Window {
+            len: len.max(1),
+            values: VecDeque::with_capacity(len.max(1) + 1),
+            mean: 0.0,
+            m2: 0.0,
+            pushes: 0,
+        }
+    }
+
+    fn push(&mut self, x: f64) {
+        self.values.push_back(x);
+        let n = self.values.len() as f64;
+        let d = x - self.mean;
+        self.mean += d / n;
+        self.m2 += d * (x - self.mean);
+        if self.values.len() > self.len {
+            let old = self.values.pop_front().unwrap_or(0.0);
+            let n = self.values.len() as f64;
+            let d = old - self.mean;
+            self.mean -= d / n;
+            self.m2 -= d * (old - self.mean);
+        }
+        self.pushes += 1;
+        if self.pushes % self.len == 0 {
+            self.rebuild();
+        }
+    }
+
+    fn rebuild(&mut self) {
+        let n = self.values.len() as f64;
+        self.mean = self.values.iter().sum::<f64>() / n;
+        let mean = self.mean;
+        self.m2 = self.values.iter().map(|x| (x - mean) * (x - mean)).sum();
+    }
+
+    fn is_full(&self) -> bool {
+        self.values.len() == self.len
+    }
+
+    fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// The population standard deviation. Rounding can leave the squared
+    /// deviations a hair below zero, so that's clamped.
+    fn std_dev(&self) -> f64 {
+        (self.m2 / self.values.len() as f64).max(0.0).sqrt()
+    }
+}
+
+/// Simple moving average over the last `len` prices.
+#[derive(Debug, Clone)]
+pub struct Sma {
+    window: Window,
+}
+
+impl Sma {
+    pub fn new(len: usize) -> Sma {
+        Sma { window: Window::new(len) }
+    }
+}
+
+impl Indicator for Sma {
+    type Output = f64;
+
+    fn update(&mut self, price: f64) {
+        self.window.push(price);
+    }
+
+    fn value(&self) -> Option<f64> {
+        if self.window.is_full() {
+            Some(self.window.mean())
+        } else {
+            None
+        }
+    }
+}
+
+/// Exponential moving average, seeded with the first price.
+#[derive(Debug, Clone)]
+pub struct Ema {
+    alpha: f64,
+    value: Option<f64>,
+}
+
+impl Ema {
+    /// The usual `2 / (len + 1)` smoothing.
+    pub fn new(len: usize) -> Ema {
+        Ema::with_alpha(2.0 / (len as f64 + 1.0))
+    }
+
+    /// How much weight each new price gets, between 0 and 1.
+    pub fn with_alpha(alpha: f64) -> Ema {
+        Ema {
+            alpha: alpha,
+            value: None,
+        }
+    }
+}
+
+impl Indicator for Ema {
+    type Output = f64;
+
+    fn update(&mut self, price: f64) {
+        self.value = Some(match self.value {
+            Some(v) => v + self.alpha * (price - v),
+            None => price,
+        });
+    }
+
+    fn value(&self) -> Option<f64> {
+        self.value
+    }
+}
+
+/// Standard deviation of the log returns between the last `len + 1`
+/// prices. It's per update, so scale it by the update rate for anything
+/// longer.
+#[derive(Debug, Clone)]
+pub struct Volatility {
+    last: Option<f64>,
+    returns: Window,
+}
+
+impl Volatility {
+    pub fn new(len: usize) -> Volatility {
+        Volatility {
+            last: None,
+            returns: Window::new(len),
+        }
+    }
+}
+
+impl Indicator for Volatility {
+    type Output = f64;
+
+    fn update(&mut self, price: f64) {
+        if price <= 0.0 {
+            return;
+        }
+        if let Some(last) = self.last {
+            self.returns.push((price / last).ln());
+        }
+        self.last = Some(price);
+    }
+
+    fn value(&self) -> Option<f64> {
+        if self.returns.is_full() {
+            Some(self.returns.std_dev())
+        } else {
+            None
+        }
+    }
+}
+
+/// Bollinger bands: a moving average and `k` standard deviations either
+/// side of it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bands {
+    pub lower: f64,
+    pub middle: f64,
+    pub upper: f64,
+}
+
+impl Bands {
+    /// Where a price sits between the bands, 0 at the lower and 1 at the
+    /// upper. Outside the bands it's below 0 or above 1.
+    pub fn percent_b(&self, price: f64) -> Option<f64> {
+        let width = self.upper - self.lower;
+        if width > 0.0 {
+            Some((price - self.lower) / width)
+        } else {
+            None
+        }
+    }
+}
+
+/// Bollinger bands over the last `len` prices.
+#[derive(Debug, Clone)]
+pub struct Bollinger {
+    k: f64,
+    window: Window,
+}
+
+impl Bollinger {
+    pub fn new(len: usize, k: f64) -> Bollinger {
+        Bollinger {
+            k: k,
+            window: Window::new(len),
+        }
+    }
+}
+
+impl Indicator for Bollinger {
+    type Output = Bands;
+
+    fn update(&mut self, price: f64) {
+        self.window.push(price);
+    }
+
+    fn value(&self) -> Option<Bands> {
+        if !self.window.is_full() {
+            return None;
+        }
+        let middle = self.window.mean();
+        let spread = self.k * self.window.std_dev();
+        Some(Bands {
+            lower: middle - spread,
+            middle: middle,
+            upper: middle + spread,
+        })
+    }
+}
+
+/// Wilder's relative strength index, from 0 to 100.
+#[derive(Debug, Clone)]
+pub struct Rsi {
+    len: usize,
+    last: Option<f64>,
+    /// Price changes seen so far, up to `len`.
+    seen: usize,
+    gain: f64,
+    loss: f64,
+}
+
+impl Rsi {
+    pub fn new(len: usize) -> Rsi {
+        Rsi {
+            len: len.max(1),
+            last: None,
+            seen: 0,
+            gain: 0.0,
+            loss: 0.0,
+        }
+    }
+}
+
+impl Indicator for Rsi {
+    type Output = f64;
+
+    fn update(&mut self, price: f64) {
+        let change = match self.last {
+            Some(last) => price - last,
+            None => {
+                self.last = Some(price);
+                return;
+            }
+        };
+        self.last = Some(price);
+        let (gain, loss) = (change.max(0.0), (-change).max(0.0));
+        let n = self.len as f64;
+        if self.seen < self.len {
+            // Plain average for the first `len` changes.
+            self.seen += 1;
+            self.gain += (gain - self.gain) / self.seen as f64;
+            self.loss += (loss - self.loss) / self.seen as f64;
+        } else {
+            self.gain = (self.gain * (n - 1.0) + gain) / n;
+            self.loss = (self.loss * (n - 1.0) + loss) / n;
+        }
+    }
+
+    fn value(&self) -> Option<f64> {
+        if self.seen < self.len {
+            None
+        } else if self.loss == 0.0 {
+            Some(if self.gain == 0.0 { 50.0 } else { 100.0 })
+        } else {
+            Some(100.0 - 100.0 / (1.0 + self.gain / self.loss))
+        }
+    }
+}
+
+/// Volume weighted average price over the last `len` trades or bars.
+#[derive(Debug, Clone)]
+pub struct RollingVwap {
+    len: usize,
+    trades: VecDeque<(u64, u64)>,
+    notional: u64,
+    volume: u64,
+    last_trade: Option<DateTime<UTC>>,
+}
+
+impl RollingVwap {
+    pub fn new(len: usize) -> RollingVwap {
+        RollingVwap {
+            len: len.max(1),
+            trades: VecDeque::with_capacity(len.max(1) + 1),
+            notional: 0,
+            volume: 0,
+            last_trade: None,
+        }
+    }
+
+    /// Add a trade.
+    pub fn update(&mut self, price: Price, qty: Qty) {
+        self.push(price.0 * qty.0, qty.0);
+    }
+
+    /// Add a whole bar as one entry in the window.
+    pub fn on_bar(&mut self, bar: &Bar) {
+        self.push(bar.notional, bar.volume.0);
+    }
+
+    /// Add a quote's last trade, unless it's already been added.
+    pub fn on_quote(&mut self, quote: &QuoteResponse) {
+        if let (Some(p), Some(n), Some(t)) = (quote.last, quote.last_size, quote.last_trade) {
+            if self.last_trade.map_or(true, |seen| t > seen) {
+                self.last_trade = Some(t);
+                self.update(p, n);
+            }
+        }
+    }
+
+    /// The vwap in cents, once anything with size has traded.
+    pub fn value(&self) -> Option<f64> {
+        if self.volume == 0 {
+            None
+        } else {
+            Some(self.notional as f64 / self.volume as f64)
+        }
+    }
+
+    fn push(&mut self, notional: u64, volume: u64) {
+        self.trades.push_back((notional, volume));
+        self.notional += notional;
+        self.volume += volume;
+        if self.trades.len() > self.len {
+            if let Some((n, v)) = self.trades.pop_front() {
+                self.notional -= n;
+                self.volume -= v;
+            }
+        }
+    }
+}
+
+/// How lopsided the top of a quote is, from -1 (all asks) to 1 (all bids).
+pub fn quote_imbalance(quote: &QuoteResponse) -> Option<f64> {
+    imbalance(quote.bid_size.0, quote.ask_size.0)
+}
+
+/// Like `quote_imbalance`, over the best `depth` levels of each side of a
+/// book.
+pub fn book_imbalance(book: &OrderbookResponse, depth: usize) -> Option<f64> {
+    let size = |side: &Option<Vec<BidAsk>>| {
+        side.iter().flat_map(|s| s.iter().take(depth)).map(|l| l.qty.0).sum()
+    };
+    imbalance(size(&book.bids), size(&book.asks))
+}
+
+fn imbalance(bids: u64, asks: u64) -> Option<f64> {
+    if bids + asks == 0 {
+        None
+    } else {
+        Some((bids as f64 - asks as f64) / (bids + asks) as f64)
+    }
+}
+
+/// The midpoint weighted towards the side with less size, where the next
+/// trade is more likely to take the price.
+pub fn microprice(quote: &QuoteResponse) -> Option<f64> {
+    match (quote.bid, quote.ask) {
+        (Some(b), Some(a)) => weighted(b, quote.bid_size, a, quote.ask_size),
+        _ => None,
+    }
+}
+
+/// `microprice` from the top level of each side of a book.
+pub fn book_microprice(book: &OrderbookResponse) -> Option<f64> {
+    let top = |side: &Option<Vec<BidAsk>>| {
+        side.as_ref().and_then(|s| s.first()).map(|l| (l.price, l.qty))
+    };
+    match (top(&book.bids), top(&book.asks)) {
+        (Some((b, bq)), Some((a, aq))) => weighted(b, bq, a, aq),
+        _ => None,
+    }
+}
+
+fn weighted(bid: Price, bid_size: Qty, ask: Price, ask_size: Qty) -> Option<f64> {
+    let total = bid_size.0 + ask_size.0;
+    if total == 0 {
+        return Some((bid.0 + ask.0) as f64 / 2.0);
+    }
+    Some((bid.0 as f64 * ask_size.0 as f64 + ask.0 as f64 * bid_size.0 as f64) / total as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bars::{BarBuilder, BarSpec};
+    use chrono::{DateTime, UTC};
+    use data::{BidAsk, OrderbookResponse, QuoteResponse};
+    use types::{Price, Qty};
+
+    fn at(second: u32) -> DateTime<UTC> {
+        format!("2016-06-02T16:20:{:02}Z", second).parse().unwrap()
+    }
+
+    fn quote(bid: u64, bid_size: u64, ask: u64, ask_size: u64) -> QuoteResponse {
+        QuoteResponse {
+            ok: true,
+            symbol: "FOOBAR".into(),
+            venue: "TESTEX".into(),
+            bid: Some(Price(bid)),
+            ask: Some(Price(ask)),
+            bid_size: Qty(bid_size),
+            ask_size: Qty(ask_size),
+            bid_depth: Qty(bid_size),
+            ask_depth: Qty(ask_size),
+            last: None,
+            last_size: None,
+            last_trade: None,
+            quote_time: None,
+        }
+    }
+
+    fn feed<I: Indicator>(mut i: I, prices: &[f64]) -> I {
+        for &p in prices {
+            i.update(p);
+        }
+        i
+    }
+
+    #[test]
+    fn test_averages() {
+        let sma = feed(Sma::new(3), &[1.0, 2.0]);
+        assert_eq!(None, sma.value());
+        assert_eq!(Some(3.0), feed(sma, &[3.0, 4.0]).value());
+
+        let ema = feed(Ema::with_alpha(0.5), &[10.0, 20.0, 20.0]);
+        assert_eq!(Some(17.5), ema.value());
+
+        let mut ema = Ema::new(3);
+        ema.on_quote(&quote(100, 1, 102, 1));
+        assert_eq!(Some(101.0), ema.value());
+    }
+
+    #[test]
+    fn test_window_doesnt_drift() {
+        // A long session a cent either side of $100,000.00.
+        let mut w = Window::new(20);
+        for i in 0..1000007 {
+            w.push(1e7 + (i % 2) as f64);
+        }
+        assert!(w.is_full());
+        assert!((w.mean() - (1e7 + 0.5)).abs() < 1e-6);
+        assert!((w.std_dev() - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_spread_indicators() {
+        let b = feed(Bollinger::new(4, 2.0), &[2.0, 4.0, 4.0, 6.0]).value().unwrap();
+        let sd = 2f64.sqrt();
+        assert_eq!(4.0, b.middle);
+        assert!((b.upper - (4.0 + 2.0 * sd)).abs() < 1e-9);
+        assert!((b.lower - (4.0 - 2.0 * sd)).abs() < 1e-9);
+        assert!((b.percent_b(4.0).unwrap() - 0.5).abs() < 1e-9);
+
+        let flat = feed(Volatility::new(2), &[100.0, 100.0, 100.0]);
+        assert_eq!(Some(0.0), flat.value());
+        let moving = feed(Volatility::new(2), &[100.0, 110.0, 100.0]);
+        assert!(moving.value().unwrap() > 0.09);
+    }
+
+    #[test]
+    fn test_rsi() {
+        assert_eq!(Some(100.0), feed(Rsi::new(3), &[1.0, 2.0, 3.0, 4.0]).value());
+        let rsi = feed(Rsi::new(2), &[10.0, 12.0, 11.0]);
+        // Average gain 1, average loss 0.5.
+        assert!((rsi.value().unwrap() - 200.0 / 3.0).abs() < 1e-9);
+        assert_eq!(None, feed(Rsi::new(2), &[10.0, 12.0]).value());
+    }
+
+    #[test]
+    fn test_vwap_and_book() {
+        let mut bars = BarBuilder::new(BarSpec::Ticks(2));
+        let mut vwap = RollingVwap::new(1);
+        bars.on_trade(at(0), Price(100), Qty(1));
+        vwap.on_bar(&bars.on_trade(at(1), Price(200), Qty(3)).unwrap());
+        assert_eq!(Some(175.0), vwap.value());
+
+        let mut q = quote(100, 1, 102, 1);
+        q.last = Some(Price(300));
+        q.last_size = Some(Qty(1));
+        q.last_trade = Some(at(2));
+        vwap.on_quote(&q);
+        vwap.on_quote(&q);
+        assert_eq!(Some(300.0), vwap.value());
+
+        let q = quote(100, 300, 110, 100);
+        assert_eq!(Some(0.5), quote_imbalance(&q));
+        assert_eq!(Some(107.5), microprice(&q));
+
+        let level = |p, n| BidAsk { price: Price(p), qty: Qty(n), is_buy: true };
+        let book = OrderbookResponse {
+            ok: true,
+            venue: "TESTEX".into(),
+            symbol: "FOOBAR".into(),
+            bids: Some(vec![level(100, 10), level(99, 80)]),
+            asks: Some(vec![level(101, 30)]),
+            ts: "2015-12-04T09:02:16.680986205Z".parse().unwrap(),
+        };
+        assert_eq!(Some(-0.5), book_imbalance(&book, 1));
+        assert_eq!(Some(0.5), book_imbalance(&book, 2));
+        assert_eq!(Some(100.25), book_microprice(&book));
+    }
+}
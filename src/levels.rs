@@ -0,0 +1,615 @@
+//! Solvers for the classic Stockfighter levels.
+//!
+//! Each one starts its level with `Client::start_level`, trades through the
+//! `LevelClient` it gets back and watches the game master's flash messages
+//! until the level says it's done or `SolveConfig::time_limit` runs out.
+//! They're meant as worked examples as much as anything.
+//!
+//! ```no_run
+//! use michromer::client::Client;
+//! use michromer::levels::{self, SolveConfig};
+//!
+//! let client = Client::from_config().unwrap();
+//! let outcome = levels::solve(&client, "chock_a_block", &SolveConfig::default()).unwrap();
+//! println!("bought {} shares", outcome.bought);
+//! ```
+
+use brief::LevelBrief;
+use client::{Client, LevelClient};
+use data::{OrderDirection, OrderResponse, OrderType, QuoteResponse};
+use error::{Error, Result};
+use flash::{FlashWatcher, LevelHint};
+use http::HttpClient;
+use order::OrderBuilder;
+use std::collections::BTreeMap;
+use std::thread;
+use std::time::{Duration, Instant};
+use types::{OrderId, Price, Qty, Symbol, Venue};
+
+/// Every level with a solver, in the order the game plays them.
+pub static LEVELS: &'static [&'static str] = &["first_steps",
+                                               "chock_a_block",
+                                               "sell_side",
+                                               "dueling_bulldozers",
+                                               "irrational_exuberance"];
+
+/// How long the solvers wait and how long they keep going.
+#[derive(Debug, Clone)]
+pub struct SolveConfig {
+    /// Time between looking at the market.
+    pub poll_interval: Duration,
+    /// Give up after this long, even if the level isn't done.
+    pub time_limit: Duration,
+}
+
+impl Default for SolveConfig {
+    fn default() -> SolveConfig {
+        SolveConfig {
+            poll_interval: Duration::from_millis(250),
+            time_limit: Duration::from_secs(600),
+        }
+    }
+}
+
+/// Where a solver got to.
+#[derive(Serialize, Debug, Clone)]
+pub struct Outcome {
+    pub level: String,
+    pub instance_id: i64,
+    /// The game master said the level is over.
+    pub done: bool,
+    pub bought: Qty,
+    pub sold: Qty,
+    /// Cents received minus cents paid.
+    pub cash: i64,
+    /// The last value of every hint from the flash messages.
+    pub hints: Vec<LevelHint>,
+}
+
+impl Outcome {
+    /// Shares held at the end, negative when short.
+    pub fn position(&self) -> i64 {
+        self.bought.0 as i64 - self.sold.0 as i64
+    }
+}
+
+/// Start the level called `name` and run its solver.
+///
+/// # Errors
+///
+/// Errors out when:
+///  there's no solver for the level
+///  starting the level or any call made while solving it fails
+pub fn solve<T: HttpClient + Clone>(client: &Client<T>,
+                                    name: &str,
+                                    config: &SolveConfig)
+                                    -> Result<Outcome> {
+    match name {
+        "first_steps" => first_steps(client, config),
+        "chock_a_block" => chock_a_block(client, config),
+        "sell_side" => sell_side(client, config),
+        "dueling_bulldozers" => dueling_bulldozers(client, config),
+        "irrational_exuberance" => irrational_exuberance(client, config),
+        _ => {
+            Err(Error::Config(format!("no solver for level {}, try one of {}",
+                                      name,
+                                      LEVELS.join(", "))))
+        }
+    }
+}
+
+/// Buy the shares the level asks for, 100 unless the instructions say
+/// otherwise, with a market order. If it closes without filling in full,
+/// another one goes out for the rest.
+pub fn first_steps<T: HttpClient + Clone>(client: &Client<T>,
+                                          config: &SolveConfig)
+                                          -> Result<Outcome> {
+    let mut s = try!(Session::start(client, "first_steps", config));
+    let qty = s.brief.target_qty.unwrap_or(Qty(100));
+    while s.bought() < qty && !try!(s.finished()) {
+        if !s.has_open() {
+            let order = s.lc.buy().qty(qty - s.bought()).order_type(OrderType::Market);
+            try!(s.send(order));
+        }
+        try!(s.refresh());
+    }
+    Ok(s.outcome())
+}
+
+/// Buy a large block without paying more than the target price.
+///
+/// The target only shows up in the flash messages once some shares have
+/// been bought, so small lots are bought at the ask until it does. After
+/// that, anything offered at or below the target is taken with
+/// immediate-or-cancel orders.
+pub fn chock_a_block<T: HttpClient + Clone>(client: &Client<T>,
+                                            config: &SolveConfig)
+                                            -> Result<Outcome> {
+    let mut s = try!(Session::start(client, "chock_a_block", config));
+    let goal = s.brief.target_qty.unwrap_or(Qty(100000));
+    while s.bought() < goal && !try!(s.finished()) {
+        let q = try!(s.quote());
+        if let Some(ask) = q.ask {
+            let lot = match s.target_price() {
+                Some(target) if ask > target => Qty(0),
+                Some(_) => Qty(2000),
+                None => Qty(100),
+            };
+            let qty = [lot, q.ask_size, goal - s.bought()].iter().cloned().min().unwrap();
+            if !qty.is_zero() {
+                try!(s.send(s.lc
+                    .buy()
+                    .price(ask)
+                    .qty(qty)
+                    .order_type(OrderType::ImmediateOrCancel)));
+            }
+        }
+        s.sleep();
+    }
+    Ok(s.outcome())
+}
+
+/// Make a market, keeping within the level's position limit.
+pub fn sell_side<T: HttpClient + Clone>(client: &Client<T>,
+                                        config: &SolveConfig)
+                                        -> Result<Outcome> {
+    let mut s = try!(Session::start(client, "sell_side", config));
+    try!(make_market(&mut s));
+    Ok(s.outcome())
+}
+
+/// Make a market against another market maker. Same as `sell_side`, with
+/// the same position limit.
+pub fn dueling_bulldozers<T: HttpClient + Clone>(client: &Client<T>,
+                                                 config: &SolveConfig)
+                                                 -> Result<Outcome> {
+    let mut s = try!(Session::start(client, "dueling_bulldozers", config));
+    try!(make_market(&mut s));
+    Ok(s.outcome())
+}
+
+/// Push the price up to the target from the flash messages, then sell.
+///
+/// Lifts every offer, staying within the position limit, until a trade
+/// prints at or above the target. Then sells everything held back into
+/// the bids.
+pub fn irrational_exuberance<T: HttpClient + Clone>(client: &Client<T>,
+                                                    config: &SolveConfig)
+                                                    -> Result<Outcome> {
+    let mut s = try!(Session::start(client, "irrational_exuberance", config));
+    let limit = s.brief.position_limit.unwrap_or(Qty(1000)).0 as i64;
+    let mut selling = false;
+    while !try!(s.finished()) {
+        let q = try!(s.quote());
+        if !selling {
+            selling = match (s.target_price(), q.last) {
+                (Some(target), Some(last)) => last >= target,
+                _ => false,
+            };
+        }
+        if selling {
+            if s.position() <= 0 {
+                break;
+            }
+            if let Some(bid) = q.bid {
+                let qty = Qty(s.position() as u64).min(q.bid_size);
+                if !qty.is_zero() {
+                    try!(s.send(s.lc
+                        .sell()
+                        .price(bid)
+                        .qty(qty)
+                        .order_type(OrderType::ImmediateOrCancel)));
+                }
+            }
+        } else if let Some(ask) = q.ask {
+            let room = (limit - s.position()).max(0) as u64;
+            let qty = Qty(room).min(q.ask_size);
+            if !qty.is_zero() {
+                try!(s.send(s.lc
+                    .buy()
+                    .price(ask)
+                    .qty(qty)
+                    .order_type(OrderType::ImmediateOrCancel)));
+            }
+        }
+        s.sleep();
+    }
+    Ok(s.outcome())
+}
+
+/// Quote a penny inside the spread on both sides, requoting every poll
+/// and only ever offering what keeps the position inside the limit.
+fn make_market<T: HttpClient + Clone>(s: &mut Session<T>) -> Result<()> {
+    let limit = s.brief.position_limit.unwrap_or(Qty(1000)).0 as i64;
+    let lot = 100;
+    while !try!(s.finished()) {
+        try!(s.cancel_open());
+        let q = try!(s.quote());
+        if let (Some(bid), Some(ask)) = (q.bid, q.ask) {
+            if ask.0 > bid.0 + 2 {
+                let buy = (limit - s.position()).max(0).min(lot) as u64;
+                let sell = (limit + s.position()).max(0).min(lot) as u64;
+                if buy > 0 {
+                    try!(s.send(s.lc.buy().price(Price(bid.0 + 1)).qty(Qty(buy))));
+                }
+                if sell > 0 {
+                    try!(s.send(s.lc.sell().price(Price(ask.0 - 1)).qty(Qty(sell))));
+                }
+            }
+        }
+        s.sleep();
+    }
+    s.cancel_open()
+}
+
+/// A started level and everything a solver has learned about it.
+struct Session<'a, T: HttpClient + Clone + 'a> {
+    client: &'a Client<T>,
+    lc: LevelClient<T>,
+    name: String,
+    venue: Venue,
+    stock: Symbol,
+    brief: LevelBrief,
+    watcher: FlashWatcher,
+    orders: BTreeMap<OrderId, OrderResponse>,
+    config: SolveConfig,
+    started: Instant,
+    done: bool,
+}
+
+impl<'a, T: HttpClient + Clone> Session<'a, T> {
+    fn start(client: &'a Client<T>, name: &str, config: &SolveConfig) -> Result<Session<'a, T>> {
+        let lc = try!(client.start_level(name));
+        let venue = try!(lc.level
+            .venues
+            .first()
+            .cloned()
+            .ok_or_else(|| Error::Config(format!("{} started without a venue", name))));
+        let stock = try!(lc.level
+            .tickers
+            .first()
+            .cloned()
+            .ok_or_else(|| Error::Config(format!("{} started without a ticker", name))));
+        info!(level = name, instance = lc.level.instance_id, "started level");
+        Ok(Session {
+            client: client,
+            brief: LevelBrief::for_level(name, &lc.level),
+            lc: lc,
+            name: name.to_owned(),
+            venue: venue,
+            stock: stock,
+            watcher: FlashWatcher::new(),
+            orders: BTreeMap::new(),
+            config: config.clone(),
+            started: Instant::now(),
+            done: false,
+        })
+    }
+
+    /// Check in with the game master. True once the level is over or the
+    /// time limit has passed.
+    fn finished(&mut self) -> Result<bool> {
+        let status = try!(self.client.instance_status(self.lc.level.instance_id));
+        for hint in self.watcher.update(&status) {
+            info!(level = %self.name, hint = ?hint, "level hint");
+        }
+        self.done = status.done;
+        Ok(self.done || self.started.elapsed() >= self.config.time_limit)
+    }
+
+    fn quote(&self) -> Result<QuoteResponse> {
+        self.lc.quote(&self.venue, &self.stock)
+    }
+
+    fn send(&mut self, order: OrderBuilder) -> Result<OrderResponse> {
+        let order = try!(order.venue(self.venue.clone()).stock(self.stock.clone()).build());
+        let res = try!(self.lc.order(&order));
+        self.orders.insert(res.id, res.clone());
+        Ok(res)
+    }
+
+    /// Catch up on fills for every order still open, then wait a poll.
+    fn refresh(&mut self) -> Result<()> {
+        let open: Vec<OrderId> = self.orders.values().filter(|o| o.open).map(|o| o.id).collect();
+        for id in open {
+            let res = try!(self.lc.order_status(&self.venue, &self.stock, id));
+            self.orders.insert(id, res);
+        }
+        self.sleep();
+        Ok(())
+    }
+
+    fn has_open(&self) -> bool {
+        self.orders.values().any(|o| o.open)
+    }
+
+    fn cancel_open(&mut self) -> Result<()> {
+        let open: Vec<OrderId> = self.orders.values().filter(|o| o.open).map(|o| o.id).collect();
+        for id in open {
+            let res = try!(self.lc.delete_order(&self.venue, &self.stock, id));
+            self.orders.insert(id, res);
+        }
+        Ok(())
+    }
+
+    fn sleep(&self) {
+        thread::sleep(self.config.poll_interval);
+    }
+
+    fn target_price(&self) -> Option<Price> {
+        self.watcher.latest().iter().filter_map(|h| match *h {
+            LevelHint::TargetPrice(p) => Some(p),
+            _ => None,
+        }).next()
+    }
+
+    fn filled(&self, buys: bool) -> Qty {
+        self.orders
+            .values()
+            .filter(|o| match o.direction {
+                Some(OrderDirection::Buy) => buys,
+                Some(OrderDirection::Sell) => !buys,
+                None => false,
+            })
+            .map(|o| o.total_filled)
+            .sum()
+    }
+
+    fn bought(&self) -> Qty {
+        self.filled(true)
+    }
+
+    fn position(&self) -> i64 {
+        self.filled(true).0 as i64 - self.filled(false).0 as i64
+    }
+
+    fn outcome(self) -> Outcome {
+        let cash = self.orders
+            .values()
+            .map(|o| {
+                let value: i64 = o.fills.iter().map(|f| (f.price.0 * f.qty.0) as i64).sum();
+                match o.direction {
+                    Some(OrderDirection::Buy) => -value,
+                    _ => value,
+                }
+            })
+            .sum();
+        Outcome {
+            level: self.name.clone(),
+            instance_id: self.lc.level.instance_id,
+            done: self.done,
+            bought: self.filled(true),
+            sold: self.filled(false),
+            cash: cash,
+            hints: self.watcher.latest().to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::UTC;
+    use data::{Fill, InstanceStatusResponse, Order};
+    use endpoint::{ApiProfile, Endpoint};
+    use error::Result;
+    use flash::LevelHint;
+    use http::HttpClient;
+    use serde_json;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+    use std::time::Duration;
+    use test_support::{response, test_level};
+    use types::{OrderId, Price, Qty};
+
+    const BASE: &'static str = "http://localhost:8000";
+
+    /// A game with one venue whose book only moves when a buy lifts the
+    /// offer, which pushes it up 50 cents.
+    #[derive(Debug)]
+    struct SimState {
+        instructions: String,
+        flash: String,
+        /// The level is done after this many status checks.
+        polls_left: u32,
+        bid: u64,
+        ask: u64,
+        size: u64,
+        last: Option<u64>,
+        /// Limit orders on one side, buys if this is set, fill in full. The
+        /// other side's rest until they're canceled.
+        limit_buys_fill: bool,
+        orders: Vec<OrderResponse>,
+    }
+
+    #[derive(Debug, Clone)]
+    struct TestSim(Rc<RefCell<SimState>>);
+
+    impl TestSim {
+        fn new(instructions: &str, flash: &str, polls: u32) -> TestSim {
+            TestSim(Rc::new(RefCell::new(SimState {
+                instructions: instructions.to_owned(),
+                flash: flash.to_owned(),
+                polls_left: polls,
+                bid: 4990,
+                ask: 5000,
+                size: 500,
+                last: None,
+                limit_buys_fill: true,
+                orders: Vec::new(),
+            })))
+        }
+
+        fn client(&self) -> Client<TestSim> {
+            Client::with_http_client(self.clone(), BASE, ApiProfile::default())
+        }
+    }
+
+    impl HttpClient for TestSim {
+        fn get(&self, url: &str) -> Result<String> {
+            let mut s = self.0.borrow_mut();
+            match Endpoint::parse(&url[BASE.len()..]) {
+                Ok(Endpoint::InstanceStatus { instance: 7 }) => {
+                    s.polls_left = s.polls_left.saturating_sub(1);
+                    let mut flash = HashMap::new();
+                    flash.insert("info".to_owned(), s.flash.clone());
+                    Ok(serde_json::to_string(&InstanceStatusResponse {
+                            ok: true,
+                            done: s.polls_left == 0,
+                            id: 7,
+                            state: "open".to_owned(),
+                            details: None,
+                            flash: Some(flash),
+                        })
+                        .unwrap())
+                }
+                Ok(Endpoint::Quote { venue, stock }) => {
+                    Ok(serde_json::to_string(&QuoteResponse {
+                            ok: true,
+                            symbol: stock,
+                            venue: venue,
+                            bid: Some(Price(s.bid)),
+                            ask: Some(Price(s.ask)),
+                            bid_size: Qty(s.size),
+                            ask_size: Qty(s.size),
+                            bid_depth: Qty(s.size),
+                            ask_depth: Qty(s.size),
+                            last: s.last.map(Price),
+                            last_size: None,
+                            last_trade: None,
+                            quote_time: None,
+                        })
+                        .unwrap())
+                }
+                _ => panic!("unexpected get {}", url),
+            }
+        }
+        fn post(&self, url: &str, body: Option<&str>) -> Result<String> {
+            let mut s = self.0.borrow_mut();
+            if let Ok(Endpoint::StartLevel { .. }) = Endpoint::parse(&url[BASE.len()..]) {
+                let mut level = test_level(&["TESTEX"]);
+                level.instance_id = 7;
+                level.instructions.insert("Instructions".to_owned(), s.instructions.clone());
+                return Ok(serde_json::to_string(&level).unwrap());
+            }
+            let o: Order = serde_json::from_str(body.unwrap()).unwrap();
+            let buy = matches!(o.direction, OrderDirection::Buy);
+            let limit = matches!(o.order_type, OrderType::Limit);
+            let fill = match o.order_type {
+                OrderType::Limit if buy == s.limit_buys_fill => Some((o.price.0, o.qty.0)),
+                OrderType::Limit => None,
+                _ if buy => Some((s.ask, s.size.min(o.qty.0))),
+                _ => Some((s.bid, s.size.min(o.qty.0))),
+            };
+            let fills: Vec<Fill> = fill.into_iter()
+                .map(|(price, qty)| {
+                    Fill {
+                        price: Price(price),
+                        qty: Qty(qty),
+                        ts: UTC::now(),
+                    }
+                })
+                .collect();
+            if buy && !limit {
+                s.last = Some(s.ask);
+                s.bid += 50;
+                s.ask += 50;
+            }
+            let mut res = response(o, fills);
+            res.id = OrderId(s.orders.len() as u64 + 1);
+            res.open = limit && res.fills.is_empty();
+            s.orders.push(res.clone());
+            Ok(serde_json::to_string(&res).unwrap())
+        }
+        fn delete(&self, url: &str) -> Result<String> {
+            let mut s = self.0.borrow_mut();
+            let id = match Endpoint::parse(&url[BASE.len()..]) {
+                Ok(Endpoint::Order { id, .. }) => id,
+                _ => panic!("unexpected delete {}", url),
+            };
+            let order = &mut s.orders[id.0 as usize - 1];
+            order.open = false;
+            Ok(serde_json::to_string(order).unwrap())
+        }
+    }
+
+    fn config() -> SolveConfig {
+        SolveConfig {
+            poll_interval: Duration::from_millis(1),
+            time_limit: Duration::from_secs(5),
+        }
+    }
+
+    #[test]
+    fn test_first_steps() {
+        let sim = TestSim::new("", "NAV: $0.00", 1000);
+        let outcome = solve(&sim.client(), "first_steps", &config()).unwrap();
+        assert_eq!(7, outcome.instance_id);
+        assert_eq!(Qty(100), outcome.bought);
+        assert_eq!(100, outcome.position());
+        assert_eq!(-500000, outcome.cash);
+        assert!(!outcome.done);
+
+        // The first order only gets 60 shares, so a second buys the rest.
+        let sim = TestSim::new("", "", 1000);
+        sim.0.borrow_mut().size = 60;
+        let outcome = solve(&sim.client(), "first_steps", &config()).unwrap();
+        assert_eq!(Qty(100), outcome.bought);
+        assert_eq!(-(60 * 5000 + 40 * 5050), outcome.cash);
+        assert_eq!(2, sim.0.borrow().orders.len());
+    }
+
+    #[test]
+    fn test_chock_a_block() {
+        let sim = TestSim::new("Buy 1,000 shares.", "Our target price is $51.00.", 1000);
+        let outcome = solve(&sim.client(), "chock_a_block", &config()).unwrap();
+        assert_eq!(Qty(1000), outcome.bought);
+        assert_eq!(-(500 * 5000 + 500 * 5050), outcome.cash);
+        assert_eq!(vec![LevelHint::TargetPrice(Price(5100))], outcome.hints);
+        // Nothing more once the offer is above the target.
+        let sim = TestSim::new("Buy 1,000 shares.", "Our target price is $49.00.", 20);
+        let outcome = solve(&sim.client(), "chock_a_block", &config()).unwrap();
+        assert_eq!(Qty(0), outcome.bought);
+        assert!(outcome.done);
+    }
+
+    #[test]
+    fn test_market_making_keeps_to_limit() {
+        let sim = TestSim::new("", "", 15);
+        let outcome = solve(&sim.client(), "sell_side", &config()).unwrap();
+        assert!(outcome.done);
+        assert_eq!(1000, outcome.position());
+        assert_eq!(Qty(0), outcome.sold);
+        assert_eq!(-4991 * 1000, outcome.cash);
+        assert!(sim.0.borrow().orders.iter().all(|o| !o.open));
+
+        let sim = TestSim::new("", "", 15);
+        sim.0.borrow_mut().limit_buys_fill = false;
+        let outcome = solve(&sim.client(), "dueling_bulldozers", &config()).unwrap();
+        assert!(outcome.done);
+        assert_eq!(-1000, outcome.position());
+        assert_eq!(Qty(0), outcome.bought);
+        assert_eq!(4999 * 1000, outcome.cash);
+        assert!(sim.0.borrow().orders.iter().all(|o| !o.open));
+    }
+
+    #[test]
+    fn test_irrational_exuberance() {
+        let sim = TestSim::new("", "The target price is $51.00.", 1000);
+        sim.0.borrow_mut().size = 400;
+        let outcome = solve(&sim.client(), "irrational_exuberance", &config()).unwrap();
+        // Stops buying at the limit, then sells it all once a trade prints
+        // at the target.
+        assert_eq!(Qty(1000), outcome.bought);
+        assert_eq!(0, outcome.position());
+        assert_eq!(1000 * 5140 - (400 * 5000 + 400 * 5050 + 200 * 5100), outcome.cash);
+        assert!(!outcome.done);
+    }
+
+    #[test]
+    fn test_unknown_level() {
+        let sim = TestSim::new("", "", 1000);
+        assert!(solve(&sim.client(), "making_amends", &SolveConfig::default()).is_err());
+    }
+}
@@ -3,17 +3,58 @@ extern crate serde_derive;
 extern crate serde_json;
 extern crate serde;
 
-#[macro_use]
 extern crate hyper;
 
 
 #[macro_use]
-extern crate log;
+extern crate tracing;
+#[cfg(feature = "otel")]
+extern crate opentelemetry;
+#[cfg(feature = "otel")]
+extern crate opentelemetry_otlp;
+#[cfg(feature = "otel")]
+extern crate opentelemetry_sdk;
+#[cfg(feature = "otel")]
+extern crate tracing_opentelemetry;
+#[cfg(feature = "otel")]
+extern crate tracing_subscriber;
 
 extern crate chrono;
+extern crate percent_encoding;
+extern crate rustls;
+extern crate toml;
+extern crate webpki_roots;
+extern crate zeroize;
 
 
+pub mod arb;
+pub mod backtest;
+pub mod bars;
+pub mod brief;
 pub mod client;
+pub mod clock;
+pub mod config;
+pub mod consolidated;
 pub mod data;
+pub mod endpoint;
 pub mod error;
+pub mod feed;
+pub mod flash;
 pub mod http;
+pub mod indicators;
+pub mod levels;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod middleware;
+pub mod order;
+pub mod router;
+pub mod secret;
+pub mod strategy;
+pub mod surveillance;
+#[cfg(feature = "otel")]
+pub mod telemetry;
+#[cfg(test)]
+mod test_support;
+pub mod transport;
+pub mod types;
+pub mod watchdog;
@@ -0,0 +1,419 @@
+//! Prometheus metrics for the api calls a `LevelClient` makes, and gauges
+//! for how a bot is trading.
+//!
+//! Everything is kept in one process wide `Metrics`, from `global()`.
+//! `serve` exposes it in the Prometheus text format.
+
+use data::{OrderDirection, OrderResponse};
+use error::{Error, Result};
+use hyper::server::{Request, Response, Server};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt::Write;
+use std::mem;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use types::{OrderId, Symbol, Venue};
+
+/// Upper bounds, in seconds, of the request latency histogram buckets.
+static LATENCY_BUCKETS: &'static [f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5,
+                                           5.0, 10.0];
+
+/// How many closed orders `record_fills` remembers.
+const CLOSED_ORDERS: usize = 10000;
+
+static GLOBAL: OnceLock<Metrics> = OnceLock::new();
+
+/// The metrics every client in this process records into.
+pub fn global() -> &'static Metrics {
+    GLOBAL.get_or_init(Metrics::new)
+}
+
+#[derive(Debug, Clone, Default)]
+struct Histogram {
+    /// Observations in each bucket, not cumulative. The last is for
+    /// everything past the largest bound.
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        if self.buckets.is_empty() {
+            self.buckets = vec![0; LATENCY_BUCKETS.len() + 1];
+        }
+        let i = LATENCY_BUCKETS.iter().position(|&b| seconds <= b).unwrap_or(LATENCY_BUCKETS.len());
+        self.buckets[i] += 1;
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    requests: BTreeMap<(&'static str, &'static str), u64>,
+    errors: BTreeMap<(&'static str, &'static str), u64>,
+    latency: BTreeMap<&'static str, Histogram>,
+    open_orders: BTreeMap<(Venue, Symbol), i64>,
+    position: BTreeMap<(Venue, Symbol), i64>,
+    cash: BTreeMap<(Venue, Symbol), i64>,
+    pnl: BTreeMap<(Venue, Symbol), i64>,
+    /// How many of each open order's fills `record_fills` has counted.
+    fills_seen: BTreeMap<(Venue, OrderId), usize>,
+    /// The most recently closed orders, oldest first, so seeing one again
+    /// doesn't count its fills twice.
+    closed: VecDeque<(Venue, OrderId)>,
+    closed_set: BTreeSet<(Venue, OrderId)>,
+    /// Shares, cash and the last fill price built up from those fills.
+    traded: BTreeMap<(Venue, Symbol), (i64, i64, i64)>,
+}
+
+/// Request counters and trading gauges.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    inner: Mutex<Inner>,
+}
+
+/// Times one request. Made by `Metrics::start`.
+#[derive(Debug)]
+pub struct Timer<'a> {
+    metrics: &'a Metrics,
+    endpoint: &'static str,
+    method: &'static str,
+    start: Instant,
+}
+
+impl<'a> Timer<'a> {
+    /// Record the request as done, and whether it failed.
+    pub fn finish(self, err: Option<&Error>) {
+        self.metrics.record_request(self.endpoint, self.method, self.start.elapsed(), err);
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        Metrics::default()
+    }
+
+    /// Start timing a request to an endpoint, named by `Endpoint::name`.
+    pub fn start<'a>(&'a self, endpoint: &'static str, method: &'static str) -> Timer<'a> {
+        Timer {
+            metrics: self,
+            endpoint: endpoint,
+            method: method,
+            start: Instant::now(),
+        }
+    }
+
+    /// Count a finished request, how long it took and what went wrong.
+    pub fn record_request(&self,
+                          endpoint: &'static str,
+                          method: &'static str,
+                          elapsed: Duration,
+                          err: Option<&Error>) {
+        let mut inner = self.inner.lock().unwrap();
+        *inner.requests.entry((endpoint, method)).or_insert(0) += 1;
+        if let Some(e) = err {
+            *inner.errors.entry((endpoint, e.kind())).or_insert(0) += 1;
+        }
+        inner.latency.entry(endpoint).or_default().observe(seconds(elapsed));
+    }
+
+    /// Count the open orders in an account's order listing for a venue.
+    ///
+    /// When the listing is for every stock on the venue, stocks missing
+    /// from it go back to zero.
+    pub fn record_orders(&self, venue: &Venue, stock: Option<&str>, orders: &[OrderResponse]) {
+        let mut inner = self.inner.lock().unwrap();
+        match stock {
+            Some(stock) => {
+                let open = orders.iter().filter(|o| o.open && o.symbol == stock).count();
+                inner.open_orders.insert((venue.clone(), stock.into()), open as i64);
+            }
+            None => {
+                for (k, v) in inner.open_orders.iter_mut() {
+                    if k.0 == *venue {
+                        *v = 0;
+                    }
+                }
+                for o in orders.iter().filter(|o| o.open) {
+                    *inner.open_orders.entry((venue.clone(), o.symbol.clone())).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+
+    pub fn set_open_orders(&self, venue: &str, stock: &str, open: i64) {
+        self.inner.lock().unwrap().open_orders.insert((venue.into(), stock.into()), open);
+    }
+
+    /// Set the number of shares held, negative when short.
+    pub fn set_position(&self, venue: &str, stock: &str, shares: i64) {
+        self.inner.lock().unwrap().position.insert((venue.into(), stock.into()), shares);
+    }
+
+    /// Set the profit or loss in cents.
+    pub fn set_pnl(&self, venue: &str, stock: &str, cents: i64) {
+        self.inner.lock().unwrap().pnl.insert((venue.into(), stock.into()), cents);
+    }
+
+    /// Set the position, cash and P&L gauges. Cash and P&L are in cents.
+    pub fn record_position(&self, venue: &str, stock: &str, position: i64, cash: i64, pnl: i64) {
+        let mut inner = self.inner.lock().unwrap();
+        let key: (Venue, Symbol) = (venue.into(), stock.into());
+        inner.position.insert(key.clone(), position);
+        inner.cash.insert(key.clone(), cash);
+        inner.pnl.insert(key, pnl);
+    }
+
+    /// Count the fills on an order that haven't been seen yet, and set the
+    /// position, cash and P&L gauges from every fill counted so far. The
+    /// shares held are marked at the last fill price.
+    ///
+    /// The same order can come back any number of times, from placing it,
+    /// checking on it or canceling it; each fill only counts once.
+    pub fn record_fills(&self, order: &OrderResponse) {
+        let sign = match order.direction {
+            Some(OrderDirection::Buy) => 1,
+            Some(OrderDirection::Sell) => -1,
+            None => return,
+        };
+        let (position, cash, pnl) = {
+            let mut guard = self.inner.lock().unwrap();
+            let inner = &mut *guard;
+            let key = (order.venue.clone(), order.id);
+            if inner.closed_set.contains(&key) {
+                return;
+            }
+            let seen = if order.open {
+                let seen = inner.fills_seen.entry(key).or_insert(0);
+                let before = *seen;
+                *seen = (*seen).max(order.fills.len());
+                before
+            } else {
+                // Closed orders don't get new fills, so stop tracking them.
+                let seen = inner.fills_seen.remove(&key).unwrap_or(0);
+                inner.closed_set.insert(key.clone());
+                inner.closed.push_back(key);
+                if inner.closed.len() > CLOSED_ORDERS {
+                    if let Some(old) = inner.closed.pop_front() {
+                        inner.closed_set.remove(&old);
+                    }
+                }
+                seen
+            };
+            if order.fills.len() <= seen {
+                return;
+            }
+            let new = &order.fills[seen..];
+            let traded = inner.traded
+                .entry((order.venue.clone(), order.symbol.clone()))
+                .or_insert((0, 0, 0));
+            for f in new {
+                let shares = sign * f.qty.0 as i64;
+                traded.0 += shares;
+                traded.1 -= shares * f.price.0 as i64;
+                traded.2 = f.price.0 as i64;
+            }
+            (traded.0, traded.1, traded.1 + traded.0 * traded.2)
+        };
+        self.record_position(&order.venue, &order.symbol, position, cash, pnl);
+    }
+
+    /// Everything in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let inner = self.inner.lock().unwrap();
+        let mut out = String::new();
+
+        header(&mut out,
+               "michromer_requests_total",
+               "counter",
+               "Api requests sent, by endpoint and method.");
+        for (&(endpoint, method), n) in &inner.requests {
+            let _ = writeln!(out,
+                             "michromer_requests_total{{endpoint=\"{}\",method=\"{}\"}} {}",
+                             endpoint,
+                             method,
+                             n);
+        }
+
+        header(&mut out,
+               "michromer_request_errors_total",
+               "counter",
+               "Api requests that failed, by endpoint and kind of error.");
+        for (&(endpoint, kind), n) in &inner.errors {
+            let _ = writeln!(out,
+                             "michromer_request_errors_total{{endpoint=\"{}\",kind=\"{}\"}} {}",
+                             endpoint,
+                             kind,
+                             n);
+        }
+
+        header(&mut out,
+               "michromer_request_duration_seconds",
+               "histogram",
+               "Time from sending an api request to parsing its response.");
+        for (endpoint, h) in &inner.latency {
+            let mut cumulative = 0;
+            for (i, n) in h.buckets.iter().enumerate() {
+                cumulative += *n;
+                let le = LATENCY_BUCKETS.get(i).map_or("+Inf".to_owned(), |b| b.to_string());
+                let _ = writeln!(out,
+                                 "michromer_request_duration_seconds_bucket{{endpoint=\"{}\",\
+                                  le=\"{}\"}} {}",
+                                 endpoint,
+                                 le,
+                                 cumulative);
+            }
+            let _ = writeln!(out,
+                             "michromer_request_duration_seconds_sum{{endpoint=\"{}\"}} {}",
+                             endpoint,
+                             h.sum);
+            let _ = writeln!(out,
+                             "michromer_request_duration_seconds_count{{endpoint=\"{}\"}} {}",
+                             endpoint,
+                             h.count);
+        }
+
+        gauge(&mut out,
+              "michromer_open_orders",
+              "Orders still open on a venue.",
+              &inner.open_orders);
+        gauge(&mut out,
+              "michromer_position_shares",
+              "Shares held, negative when short.",
+              &inner.position);
+        gauge(&mut out,
+              "michromer_cash_cents",
+              "Cash from trading, in cents.",
+              &inner.cash);
+        gauge(&mut out,
+              "michromer_pnl_cents",
+              "Profit or loss in cents.",
+              &inner.pnl);
+        out
+    }
+}
+
+fn seconds(d: Duration) -> f64 {
+    d.as_secs() as f64 + d.subsec_nanos() as f64 / 1e9
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, values: &BTreeMap<(Venue, Symbol), i64>) {
+    header(out, name, "gauge", help);
+    for (k, v) in values {
+        let _ = writeln!(out,
+                         "{}{{venue=\"{}\",stock=\"{}\"}} {}",
+                         name,
+                         escape(&k.0),
+                         escape(&k.1),
+                         v);
+    }
+}
+
+/// Escape a label value.
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Serve `global()` over http on `addr`, in the background, for Prometheus
+/// to scrape. Every path gets the metrics.
+///
+/// Returns the address actually bound, which is useful when asking for
+/// port 0.
+pub fn serve<A: ToSocketAddrs>(addr: A) -> Result<SocketAddr> {
+    let server = try!(Server::http(addr));
+    let listening = try!(server.handle_threads(scrape, 1));
+    let addr = listening.socket;
+    // Dropping `Listening` waits for the server to stop, which it never
+    // does, so leave it running for the life of the process.
+    mem::forget(listening);
+    Ok(addr)
+}
+
+fn scrape(_: Request, mut res: Response) {
+    res.headers_mut().set_raw("Content-Type", vec![b"text/plain; version=0.0.4".to_vec()]);
+    let _ = res.send(global().render().as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::UTC;
+    use data::{Fill, OrderDirection, OrderResponse, OrderType};
+    use error::Error;
+    use std::time::Duration;
+    use types::{OrderId, Price, Qty};
+
+    #[test]
+    fn test_render() {
+        let m = Metrics::new();
+        m.record_request("quote", "GET", Duration::from_millis(20), None);
+        m.record_request("quote",
+                         "GET",
+                         Duration::from_secs(20),
+                         Some(&Error::Parse("bad".to_owned())));
+        m.set_position("TESTEX", "FOO\"BAR", -100);
+        let out = m.render();
+        assert!(out.contains("michromer_requests_total{endpoint=\"quote\",method=\"GET\"} 2\n"));
+        assert!(out.contains("michromer_request_errors_total{endpoint=\"quote\",kind=\"parse\"} 1\n"));
+        assert!(out.contains("michromer_request_duration_seconds_bucket{endpoint=\"quote\",\
+                              le=\"0.025\"} 1\n"));
+        assert!(out.contains("michromer_request_duration_seconds_bucket{endpoint=\"quote\",\
+                              le=\"+Inf\"} 2\n"));
+        assert!(out.contains("michromer_request_duration_seconds_count{endpoint=\"quote\"} 2\n"));
+        assert!(out.contains("michromer_position_shares{venue=\"TESTEX\",stock=\"FOO\\\"BAR\"} \
+                              -100\n"));
+    }
+
+    #[test]
+    fn test_record_fills() {
+        let m = Metrics::new();
+        let fill = |price, qty| {
+            Fill {
+                price: Price(price),
+                qty: Qty(qty),
+                ts: UTC::now(),
+            }
+        };
+        let mut o = OrderResponse {
+            ok: true,
+            symbol: "FOOBAR".into(),
+            venue: "TESTEX".into(),
+            direction: Some(OrderDirection::Buy),
+            original_qty: Qty(30),
+            qty: Qty(20),
+            price: Price(110),
+            order_type: OrderType::Limit,
+            id: OrderId(1),
+            account: "EXB123456".into(),
+            ts: UTC::now(),
+            fills: vec![fill(100, 10)],
+            total_filled: Qty(10),
+            open: true,
+        };
+        m.record_fills(&o);
+        // The same fill again, then a new one.
+        m.record_fills(&o);
+        o.fills.push(fill(110, 20));
+        m.record_fills(&o);
+        let out = m.render();
+        assert!(out.contains("michromer_position_shares{venue=\"TESTEX\",stock=\"FOOBAR\"} 30\n"));
+        assert!(out.contains("michromer_cash_cents{venue=\"TESTEX\",stock=\"FOOBAR\"} -3200\n"));
+        assert!(out.contains("michromer_pnl_cents{venue=\"TESTEX\",stock=\"FOOBAR\"} 100\n"));
+
+        // Closing the order stops tracking it, without counting it again.
+        o.open = false;
+        m.record_fills(&o);
+        m.record_fills(&o);
+        assert!(m.inner.lock().unwrap().fills_seen.is_empty());
+        assert!(m.render()
+            .contains("michromer_position_shares{venue=\"TESTEX\",stock=\"FOOBAR\"} 30\n"));
+    }
+}
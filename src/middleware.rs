@@ -0,0 +1,523 @@
+//! Wrap any `HttpClient` in layers of middleware.
+//!
+//! A layer sees each request on its way out and each response on its way
+//! back, so retries, rate limiting, logging, recording and fault injection
+//! don't each have to implement `HttpClient` themselves.
+//!
+//! ```no_run
+//! use michromer::http::PlainHttpClient;
+//! use michromer::middleware::{Auth, Log, RateLimit, Retry, Stack};
+//! use std::time::Duration;
+//!
+//! let http = Stack::new(PlainHttpClient::new())
+//!     .layer(Log)
+//!     .layer(Retry::new(3, Duration::from_millis(100)))
+//!     .layer(RateLimit::new(Duration::from_millis(50)))
+//!     .layer(Auth::new("key"));
+//! ```
+
+use error::{Error, Result};
+use http::{DEFAULT_AUTH_HEADER, HttpClient};
+pub use http::Method;
+use secret::Secret;
+use std::fmt;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A request on its way through the stack.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub method: Method,
+    pub url: String,
+    pub body: Option<String>,
+    /// Extra headers. Values are `Secret`s so that an api key never shows
+    /// up in logs or recordings.
+    pub headers: Vec<(String, Secret)>,
+}
+
+impl Request {
+    /// Send the request with a plain `HttpClient`.
+    pub fn send<H: HttpClient + ?Sized>(&self, http: &H) -> Result<String> {
+        http.send(self.method, &self.url, self.body.as_deref(), &self.headers)
+    }
+}
+
+/// One layer of a `Stack`.
+///
+/// Most layers only need `before` and `after`. Layers that send a request
+/// more than once, or not at all, override `handle` instead.
+pub trait Middleware: Send + Sync {
+    /// Look at or change a request before it's sent. An error stops it
+    /// from being sent at all.
+    fn before(&self, _req: &mut Request) -> Result<()> {
+        Ok(())
+    }
+
+    /// Look at or change what came back.
+    fn after(&self, _req: &Request, res: Result<String>) -> Result<String> {
+        res
+    }
+
+    /// Pass the request on to the rest of the stack.
+    fn handle(&self, mut req: Request, next: Next) -> Result<String> {
+        try!(self.before(&mut req));
+        let res = next.run(req.clone());
+        self.after(&req, res)
+    }
+}
+
+/// The layers below the current one, ending with the transport.
+#[derive(Clone, Copy)]
+pub struct Next<'a> {
+    layers: &'a [Arc<dyn Middleware>],
+    transport: &'a dyn HttpClient,
+}
+
+impl<'a> Next<'a> {
+    /// Send a request through the rest of the stack.
+    pub fn run(&self, req: Request) -> Result<String> {
+        match self.layers.split_first() {
+            Some((layer, rest)) => {
+                layer.handle(req,
+                             Next {
+                                 layers: rest,
+                                 transport: self.transport,
+                             })
+            }
+            None => req.send(self.transport),
+        }
+    }
+}
+
+/// An `HttpClient` that sends everything through layers of middleware.
+///
+/// The first layer added is the outermost, so it sees requests first and
+/// responses last.
+#[derive(Clone)]
+pub struct Stack<T: HttpClient> {
+    transport: T,
+    layers: Vec<Arc<dyn Middleware>>,
+}
+
+impl<T: HttpClient> Stack<T> {
+    pub fn new(transport: T) -> Stack<T> {
+        Stack {
+            transport: transport,
+            layers: Vec::new(),
+        }
+    }
+
+    /// Add a layer inside the ones already added.
+    pub fn layer<M: Middleware + 'static>(mut self, layer: M) -> Stack<T> {
+        self.layers.push(Arc::new(layer));
+        self
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    fn call(&self, req: Request) -> Result<String> {
+        Next {
+                layers: &self.layers,
+                transport: &self.transport,
+            }
+            .run(req)
+    }
+}
+
+impl<T: HttpClient + fmt::Debug> fmt::Debug for Stack<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "Stack {{ transport: {:?}, layers: {} }}",
+               self.transport,
+               self.layers.len())
+    }
+}
+
+impl<T: HttpClient> HttpClient for Stack<T> {
+    fn get(&self, url: &str) -> Result<String> {
+        self.send(Method::Get, url, None, &[])
+    }
+    fn post(&self, url: &str, body: Option<&str>) -> Result<String> {
+        self.send(Method::Post, url, body, &[])
+    }
+    fn delete(&self, url: &str) -> Result<String> {
+        self.send(Method::Delete, url, None, &[])
+    }
+    fn send(&self,
+            method: Method,
+            url: &str,
+            body: Option<&str>,
+            headers: &[(String, Secret)])
+            -> Result<String> {
+        self.call(Request {
+            method: method,
+            url: url.to_owned(),
+            body: body.map(|b| b.to_owned()),
+            headers: headers.to_vec(),
+        })
+    }
+}
+
+/// Sends the api key in a header with every request.
+///
+/// Put it under `Recorder` and `Log` layers; they never show the key
+/// either way.
+#[derive(Debug, Clone)]
+pub struct Auth {
+    header: String,
+    key: Secret,
+}
+
+impl Auth {
+    /// Send the key in `X-Starfighter-Authorization`.
+    pub fn new<S: Into<Secret>>(key: S) -> Auth {
+        Auth {
+            header: DEFAULT_AUTH_HEADER.to_owned(),
+            key: key.into(),
+        }
+    }
+
+    /// Send the key in a different header, like an `ApiProfile`'s
+    /// `auth_header`.
+    pub fn header(mut self, name: &str) -> Auth {
+        self.header = name.to_owned();
+        self
+    }
+}
+
+impl Middleware for Auth {
+    fn before(&self, req: &mut Request) -> Result<()> {
+        req.headers.retain(|(name, _)| !name.eq_ignore_ascii_case(&self.header));
+        req.headers.push((self.header.clone(), self.key.clone()));
+        Ok(())
+    }
+}
+
+/// Logs every request and whether it worked.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Log;
+
+impl Middleware for Log {
+    fn after(&self, req: &Request, res: Result<String>) -> Result<String> {
+        match res {
+            Ok(ref body) => debug!(method = ?req.method, url = %req.url, bytes = body.len(), "sent"),
+            Err(ref e) => warn!(method = ?req.method, url = %req.url, error = %e, "failed"),
+        }
+        res
+    }
+}
+
+/// Tries again when the connection fails.
+///
+/// Only gets and deletes are retried; posting an order twice could fill
+/// it twice. Errors from the api itself, like bad json, aren't retried.
+#[derive(Debug, Clone)]
+pub struct Retry {
+    attempts: u32,
+    backoff: Duration,
+}
+
+impl Retry {
+    /// Send each request up to `attempts` times, waiting `backoff` after
+    /// the first failure and twice as long after each one after that.
+    pub fn new(attempts: u32, backoff: Duration) -> Retry {
+        Retry {
+            attempts: attempts,
+            backoff: backoff,
+        }
+    }
+}
+
+impl Middleware for Retry {
+    fn handle(&self, req: Request, next: Next) -> Result<String> {
+        let mut wait = self.backoff;
+        let mut attempt = 1;
+        loop {
+            let res = next.run(req.clone());
+            let retry = match res {
+                Err(Error::Hyper(_)) |
+                Err(Error::IO(_)) => req.method.is_idempotent() && attempt < self.attempts,
+                _ => false,
+            };
+            if !retry {
+                return res;
+            }
+            debug!(method = ?req.method, url = %req.url, attempt = attempt, "retrying");
+            thread::sleep(wait);
+            wait *= 2;
+            attempt += 1;
+        }
+    }
+}
+
+/// Spaces requests out so there's at least `interval` between the start of
+/// each one.
+#[derive(Debug)]
+pub struct RateLimit {
+    interval: Duration,
+    /// The earliest the next request can start.
+    next: Mutex<Option<Instant>>,
+}
+
+impl RateLimit {
+    pub fn new(interval: Duration) -> RateLimit {
+        RateLimit {
+            interval: interval,
+            next: Mutex::new(None),
+        }
+    }
+}
+
+impl Middleware for RateLimit {
+    fn before(&self, _req: &mut Request) -> Result<()> {
+        // Book a slot under the lock, but wait for it outside, so callers
+        // on other threads can book the slots after it meanwhile.
+        let wait = {
+            let mut next = self.next.lock().unwrap_or_else(|e| e.into_inner());
+            let now = Instant::now();
+            let start = match *next {
+                Some(n) if n > now => n,
+                _ => now,
+            };
+            *next = Some(start + self.interval);
+            start - now
+        };
+        if wait > Duration::from_millis(0) {
+            thread::sleep(wait);
+        }
+        Ok(())
+    }
+}
+
+/// A request and what came back, as seen by a `Recorder`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exchange {
+    pub request: Request,
+    /// The body, or a description of the error.
+    pub response: ::std::result::Result<String, String>,
+}
+
+/// Keeps every request and response, for tests or replaying a session.
+///
+/// Clones share the same recording, so keep one to read it back.
+#[derive(Debug, Clone, Default)]
+pub struct Recorder {
+    exchanges: Arc<Mutex<Vec<Exchange>>>,
+}
+
+impl Recorder {
+    pub fn new() -> Recorder {
+        Recorder::default()
+    }
+
+    /// Everything recorded so far.
+    pub fn exchanges(&self) -> Vec<Exchange> {
+        self.exchanges.lock().unwrap().clone()
+    }
+}
+
+impl Middleware for Recorder {
+    fn after(&self, req: &Request, res: Result<String>) -> Result<String> {
+        let response = match res {
+            Ok(ref body) => Ok(body.clone()),
+            Err(ref e) => Err(e.to_string()),
+        };
+        self.exchanges.lock().unwrap().push(Exchange {
+            request: req.clone(),
+            response: response,
+        });
+        res
+    }
+}
+
+/// Fails every `every`th request with an io error without sending it, to
+/// see how a bot copes with a flaky connection.
+#[derive(Debug)]
+pub struct InjectFaults {
+    every: u64,
+    count: Mutex<u64>,
+}
+
+impl InjectFaults {
+    pub fn every(every: u64) -> InjectFaults {
+        InjectFaults {
+            every: every,
+            count: Mutex::new(0),
+        }
+    }
+}
+
+impl Middleware for InjectFaults {
+    fn before(&self, _req: &mut Request) -> Result<()> {
+        let mut count = self.count.lock().unwrap();
+        *count += 1;
+        if self.every > 0 && *count % self.every == 0 {
+            return Err(Error::IO(io::Error::new(io::ErrorKind::ConnectionReset, "injected fault")));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use error::Result;
+    use http::HttpClient;
+    use secret::Secret;
+    use std::cell::{Cell, RefCell};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[derive(Debug, Default)]
+    struct TestHttpClient {
+        sent: Cell<u32>,
+        headers: RefCell<Vec<(String, String)>>,
+    }
+
+    impl HttpClient for TestHttpClient {
+        fn get(&self, url: &str) -> Result<String> {
+            self.sent.set(self.sent.get() + 1);
+            Ok(format!("GET {}", url))
+        }
+        fn post(&self, url: &str, body: Option<&str>) -> Result<String> {
+            self.sent.set(self.sent.get() + 1);
+            Ok(format!("POST {} {}", url, body.unwrap_or("")))
+        }
+        fn delete(&self, url: &str) -> Result<String> {
+            self.sent.set(self.sent.get() + 1);
+            Ok(format!("DELETE {}", url))
+        }
+        fn send(&self,
+                method: Method,
+                url: &str,
+                body: Option<&str>,
+                headers: &[(String, Secret)])
+                -> Result<String> {
+            *self.headers.borrow_mut() =
+                headers.iter().map(|(n, v)| (n.clone(), v.expose().to_owned())).collect();
+            match method {
+                Method::Get => self.get(url),
+                Method::Post => self.post(url, body),
+                Method::Delete => self.delete(url),
+            }
+        }
+    }
+
+    struct Rewrite;
+
+    impl Middleware for Rewrite {
+        fn before(&self, req: &mut Request) -> Result<()> {
+            req.url = req.url.replace("live", "sim");
+            Ok(())
+        }
+        fn after(&self, _req: &Request, res: Result<String>) -> Result<String> {
+            res.map(|b| b.to_uppercase())
+        }
+    }
+
+    #[test]
+    fn test_order_of_layers() {
+        let recorder = Recorder::new();
+        let http = Stack::new(TestHttpClient::default()).layer(recorder.clone()).layer(Rewrite);
+        assert_eq!("POST HTTP://SIM/ORDERS {}",
+                   http.post("http://live/orders", Some("{}")).unwrap());
+        let seen = recorder.exchanges();
+        assert_eq!(1, seen.len());
+        // The recorder is outside the rewrite, so it sees the original
+        // request and the rewritten response.
+        assert_eq!("http://live/orders", seen[0].request.url);
+        assert_eq!(Ok("POST HTTP://SIM/ORDERS {}".to_owned()), seen[0].response);
+    }
+
+    #[test]
+    fn test_retry() {
+        let http = Stack::new(TestHttpClient::default())
+            .layer(Retry::new(3, Duration::from_millis(1)))
+            .layer(InjectFaults::every(2));
+        for _ in 0..4 {
+            assert!(http.get("http://sim/quote").is_ok());
+        }
+        assert_eq!(4, http.transport().sent.get());
+
+        let http = Stack::new(TestHttpClient::default())
+            .layer(Retry::new(3, Duration::from_millis(1)))
+            .layer(InjectFaults::every(1));
+        assert!(http.get("http://sim/quote").is_err());
+        assert!(http.post("http://sim/orders", Some("{}")).is_err());
+        assert_eq!(0, http.transport().sent.get());
+    }
+
+    #[test]
+    fn test_auth() {
+        let recorder = Recorder::new();
+        let http = Stack::new(TestHttpClient::default())
+            .layer(recorder.clone())
+            .layer(Auth::new("sekrit").header("X-Api-Key"));
+        http.get("http://sim/quote").unwrap();
+        assert_eq!(vec![("X-Api-Key".to_owned(), "sekrit".to_owned())],
+                   *http.transport().headers.borrow());
+        assert!(recorder.exchanges()[0].request.headers.is_empty());
+        assert!(!format!("{:?}", Auth::new("sekrit")).contains("sekrit"));
+
+        // A transport that can't set headers won't go without the key.
+        let http = Stack::new(NoHeaders).layer(Auth::new("sekrit"));
+        assert!(http.get("http://sim/quote").is_err());
+        assert!(Stack::new(NoHeaders).get("http://sim/quote").is_ok());
+    }
+
+    /// Only knows `get`, `post` and `delete`.
+    #[derive(Debug)]
+    struct NoHeaders;
+
+    impl HttpClient for NoHeaders {
+        fn get(&self, _url: &str) -> Result<String> {
+            Ok(String::new())
+        }
+        fn post(&self, _url: &str, _body: Option<&str>) -> Result<String> {
+            Ok(String::new())
+        }
+        fn delete(&self, _url: &str) -> Result<String> {
+            Ok(String::new())
+        }
+    }
+
+    #[test]
+    fn test_rate_limit_across_threads() {
+        let limit = Arc::new(RateLimit::new(Duration::from_millis(20)));
+        let start = Instant::now();
+        let threads: Vec<_> = (0..3)
+            .map(|_| {
+                let limit = limit.clone();
+                thread::spawn(move || {
+                    let mut req = Request {
+                        method: Method::Get,
+                        url: "http://sim/quote".to_owned(),
+                        body: None,
+                        headers: vec![],
+                    };
+                    limit.before(&mut req).unwrap();
+                    start.elapsed()
+                })
+            })
+            .collect();
+        let mut started: Vec<Duration> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+        started.sort();
+        assert!(started[2] >= Duration::from_millis(40));
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let http = Stack::new(TestHttpClient::default())
+            .layer(RateLimit::new(Duration::from_millis(20)));
+        let start = Instant::now();
+        for _ in 0..3 {
+            http.get("http://sim/quote").unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+}
@@ -0,0 +1,210 @@
+use data::{Level, Order, OrderDirection, OrderType};
+use std::error::Error as StdError;
+use std::fmt;
+use types::{Account, Price, Qty, Symbol, Venue};
+
+/// Why an order was rejected before it was sent.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrderError {
+    MissingVenue,
+    MissingStock,
+    MissingDirection,
+    /// Limit, fill-or-kill and immediate-or-cancel orders need a price.
+    MissingPrice,
+    ZeroQty,
+    /// The venue isn't one of the level's venues.
+    UnknownVenue(Venue),
+    /// The stock isn't one of the level's tickers.
+    UnknownStock(Symbol),
+}
+
+impl fmt::Display for OrderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OrderError::UnknownVenue(ref v) => write!(f, "{}: {}", self.description(), v),
+            OrderError::UnknownStock(ref s) => write!(f, "{}: {}", self.description(), s),
+            _ => write!(f, "{}", self.description()),
+        }
+    }
+}
+
+impl StdError for OrderError {
+    fn description(&self) -> &str {
+        match *self {
+            OrderError::MissingVenue => "order has no venue",
+            OrderError::MissingStock => "order has no stock",
+            OrderError::MissingDirection => "order has no direction",
+            OrderError::MissingPrice => "order type needs a price",
+            OrderError::ZeroQty => "order is for zero shares",
+            OrderError::UnknownVenue(_) => "venue isn't part of this level",
+            OrderError::UnknownStock(_) => "stock isn't part of this level",
+        }
+    }
+}
+
+/// Builds an `Order`, checking it against the level it's for.
+///
+/// The account always comes from the level. When the level only has one
+/// venue or one ticker those are used unless something else is set.
+#[derive(Debug, Clone)]
+pub struct OrderBuilder {
+    account: Account,
+    venues: Vec<Venue>,
+    tickers: Vec<Symbol>,
+    venue: Option<Venue>,
+    stock: Option<Symbol>,
+    price: Option<Price>,
+    qty: Qty,
+    direction: Option<OrderDirection>,
+    order_type: OrderType,
+}
+
+impl Order {
+    /// Start building a limit order for a level.
+    pub fn builder(level: &Level) -> OrderBuilder {
+        OrderBuilder {
+            account: level.account.clone(),
+            venues: level.venues.clone(),
+            tickers: level.tickers.clone(),
+            venue: only(&level.venues),
+            stock: only(&level.tickers),
+            price: None,
+            qty: Qty(0),
+            direction: None,
+            order_type: OrderType::Limit,
+        }
+    }
+}
+
+/// The single entry of a list, if it only has one.
+fn only<T: Clone>(v: &[T]) -> Option<T> {
+    if v.len() == 1 {
+        v.first().cloned()
+    } else {
+        None
+    }
+}
+
+impl OrderBuilder {
+    pub fn venue<V: Into<Venue>>(mut self, venue: V) -> OrderBuilder {
+        self.venue = Some(venue.into());
+        self
+    }
+
+    pub fn stock<S: Into<Symbol>>(mut self, stock: S) -> OrderBuilder {
+        self.stock = Some(stock.into());
+        self
+    }
+
+    pub fn price(mut self, price: Price) -> OrderBuilder {
+        self.price = Some(price);
+        self
+    }
+
+    pub fn qty(mut self, qty: Qty) -> OrderBuilder {
+        self.qty = qty;
+        self
+    }
+
+    pub fn direction(mut self, direction: OrderDirection) -> OrderBuilder {
+        self.direction = Some(direction);
+        self
+    }
+
+    pub fn buy(self) -> OrderBuilder {
+        self.direction(OrderDirection::Buy)
+    }
+
+    pub fn sell(self) -> OrderBuilder {
+        self.direction(OrderDirection::Sell)
+    }
+
+    pub fn order_type(mut self, order_type: OrderType) -> OrderBuilder {
+        self.order_type = order_type;
+        self
+    }
+
+    /// Check everything and produce the order.
+    ///
+    /// Venues and stocks are only checked when the level lists some.
+    /// Market orders don't need a price, and any price given is dropped.
+    pub fn build(self) -> Result<Order, OrderError> {
+        let venue = try!(self.venue.ok_or(OrderError::MissingVenue));
+        let stock = try!(self.stock.ok_or(OrderError::MissingStock));
+        let direction = try!(self.direction.ok_or(OrderError::MissingDirection));
+        if !self.venues.is_empty() && !self.venues.contains(&venue) {
+            return Err(OrderError::UnknownVenue(venue));
+        }
+        if !self.tickers.is_empty() && !self.tickers.contains(&stock) {
+            return Err(OrderError::UnknownStock(stock));
+        }
+        if self.qty.is_zero() {
+            return Err(OrderError::ZeroQty);
+        }
+        let price = match self.order_type {
+            OrderType::Market => Price(0),
+            _ => try!(self.price.ok_or(OrderError::MissingPrice)),
+        };
+        Ok(Order {
+            account: self.account,
+            venue: venue,
+            stock: stock,
+            price: price,
+            qty: self.qty,
+            direction: direction,
+            order_type: self.order_type,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data::{Level, Order, OrderType};
+    use std::collections::HashMap;
+    use types::{Price, Qty};
+
+    fn level() -> Level {
+        Level {
+            ok: true,
+            instance_id: 1090,
+            account: "EXB123456".into(),
+            instructions: HashMap::new(),
+            tickers: vec!["FOOBAR".into()],
+            venues: vec!["TESTEX".into(), "OTHEREX".into()],
+        }
+    }
+
+    #[test]
+    fn test_build_limit() {
+        let o = Order::builder(&level())
+            .venue("TESTEX")
+            .buy()
+            .qty(Qty(100))
+            .price(Price(5123))
+            .build()
+            .unwrap();
+        assert_eq!("EXB123456", o.account.as_str());
+        assert_eq!("FOOBAR", o.stock.as_str());
+        assert_eq!(Price(5123), o.price);
+    }
+
+    #[test]
+    fn test_build_errors() {
+        let b = Order::builder(&level()).sell().qty(Qty(100)).price(Price(1));
+        assert_eq!(OrderError::MissingVenue, b.clone().build().unwrap_err());
+        assert_eq!(OrderError::UnknownVenue("NOPE".into()),
+                   b.clone().venue("NOPE").build().unwrap_err());
+        assert_eq!(OrderError::UnknownStock("NOPE".into()),
+                   b.clone().venue("TESTEX").stock("NOPE").build().unwrap_err());
+        assert_eq!(OrderError::ZeroQty,
+                   b.clone().venue("TESTEX").qty(Qty(0)).build().unwrap_err());
+        let no_price = Order::builder(&level())
+            .venue("TESTEX")
+            .sell()
+            .qty(Qty(100))
+            .order_type(OrderType::ImmediateOrCancel);
+        assert_eq!(OrderError::MissingPrice, no_price.clone().build().unwrap_err());
+        assert!(no_price.order_type(OrderType::Market).build().is_ok());
+    }
+}
@@ -0,0 +1,338 @@
+//! Split one large order across every venue of a level.
+//!
+//! `SmartRouter` looks at the consolidated book, sends an
+//! immediate-or-cancel order to each venue showing liquidity at a good
+//! enough price, and then tries again with whatever is left over.
+
+use client::LevelClient;
+use consolidated::{ConsolidatedBook, VenueLevel};
+use data::{Order, OrderDirection, OrderResponse, OrderType};
+use error::{Error, Result};
+use http::HttpClient;
+use types::{Price, Qty, Venue};
+
+/// A child order the router means to send.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Slice {
+    pub venue: Venue,
+    /// The worst price the slice is willing to trade at on its venue.
+    pub price: Price,
+    pub qty: Qty,
+}
+
+/// Work out how to take `qty` shares from the book.
+///
+/// Walks the other side of the consolidated ladder, best price first,
+/// stopping at `limit` if there is one. Each venue gets a single slice
+/// priced at the worst level it needs to reach, so one order sweeps
+/// everything better on that venue too. Venues come out in the order they
+/// were first reached.
+pub fn plan(book: &ConsolidatedBook,
+            direction: &OrderDirection,
+            qty: Qty,
+            limit: Option<Price>)
+            -> Vec<Slice> {
+    let ladder: Vec<VenueLevel> = match *direction {
+        OrderDirection::Buy => book.asks(),
+        OrderDirection::Sell => book.bids(),
+    };
+    let acceptable = |p: Price| match (direction, limit) {
+        (_, None) => true,
+        (&OrderDirection::Buy, Some(l)) => p <= l,
+        (&OrderDirection::Sell, Some(l)) => p >= l,
+    };
+    let mut remaining = qty;
+    let mut slices: Vec<Slice> = Vec::new();
+    for level in ladder {
+        if remaining.is_zero() || !acceptable(level.price) {
+            break;
+        }
+        let take = if level.qty < remaining { level.qty } else { remaining };
+        if take.is_zero() {
+            continue;
+        }
+        remaining -= take;
+        match slices.iter_mut().find(|s| s.venue == level.venue) {
+            Some(s) => {
+                s.price = level.price;
+                s.qty += take;
+            }
+            None => {
+                slices.push(Slice {
+                    venue: level.venue,
+                    price: level.price,
+                    qty: take,
+                })
+            }
+        }
+    }
+    slices
+}
+
+/// How a routed order went.
+#[derive(Debug)]
+pub struct RouteReport {
+    pub requested: Qty,
+    pub filled: Qty,
+    /// Every immediate-or-cancel order sent, in the order they were sent.
+    pub children: Vec<OrderResponse>,
+    /// The limit order left on the book for whatever didn't fill, if the
+    /// router was asked to leave one.
+    pub resting: Option<OrderResponse>,
+    /// What stopped the router, if something went wrong after it had
+    /// already sent orders.
+    pub error: Option<Error>,
+}
+
+impl RouteReport {
+    /// Shares that still weren't filled by the immediate-or-cancel orders.
+    pub fn remaining(&self) -> Qty {
+        self.requested.checked_sub(self.filled).unwrap_or_default()
+    }
+
+    /// Cents spent or received across every fill.
+    pub fn notional(&self) -> u64 {
+        self.children
+            .iter()
+            .flat_map(|c| c.fills.iter())
+            .map(|f| f.price.0 * f.qty.0)
+            .sum()
+    }
+
+    /// The average fill price in cents, if anything filled.
+    pub fn average_price(&self) -> Option<f64> {
+        let filled: Qty = self.children.iter().flat_map(|c| c.fills.iter()).map(|f| f.qty).sum();
+        if filled.is_zero() {
+            None
+        } else {
+            Some(self.notional() as f64 / filled.0 as f64)
+        }
+    }
+}
+
+/// Sends large orders across every venue of a level.
+#[derive(Debug)]
+pub struct SmartRouter<'a, T: HttpClient + Clone + 'a> {
+    client: &'a LevelClient<T>,
+    max_rounds: usize,
+    rest_remainder: bool,
+}
+
+impl<'a, T: HttpClient + Clone> SmartRouter<'a, T> {
+    pub fn new(client: &'a LevelClient<T>) -> SmartRouter<'a, T> {
+        SmartRouter {
+            client: client,
+            max_rounds: 3,
+            rest_remainder: false,
+        }
+    }
+
+    /// How many times to look at the books and send orders before giving
+    /// up on the rest. Defaults to 3.
+    pub fn max_rounds(mut self, rounds: usize) -> SmartRouter<'a, T> {
+        self.max_rounds = rounds;
+        self
+    }
+
+    /// Leave whatever didn't fill as a limit order at the limit price, on
+    /// the venue with the best price on our side of the book.
+    pub fn rest_remainder(mut self, rest: bool) -> SmartRouter<'a, T> {
+        self.rest_remainder = rest;
+        self
+    }
+
+    /// Buy or sell `qty` shares of `stock`, never paying more than (or
+    /// selling for less than) `limit` when one is given.
+    ///
+    /// Each round fetches every venue's order book, plans the remainder
+    /// with `plan` and sends each slice as an immediate-or-cancel order.
+    /// Rounds stop early once everything fills or no venue shows anything
+    /// at an acceptable price.
+    ///
+    /// # Errors
+    ///
+    /// Errors out when fetching a book or sending an order fails before
+    /// any order has gone out. After that, orders already sent stay sent,
+    /// so the router stops and returns the report with the failure in
+    /// `error`.
+    pub fn route(&self,
+                 direction: OrderDirection,
+                 stock: &str,
+                 qty: Qty,
+                 limit: Option<Price>)
+                 -> Result<RouteReport> {
+        let mut report = RouteReport {
+            requested: qty,
+            filled: Qty(0),
+            children: Vec::new(),
+            resting: None,
+            error: None,
+        };
+        if let Err(e) = self.route_into(&mut report, direction, stock, limit) {
+            if report.children.is_empty() && report.resting.is_none() {
+                return Err(e);
+            }
+            warn!(stock = stock, filled = report.filled.0, error = %e, "routing stopped");
+            report.error = Some(e);
+        }
+        Ok(report)
+    }
+
+    fn route_into(&self,
+                  report: &mut RouteReport,
+                  direction: OrderDirection,
+                  stock: &str,
+                  limit: Option<Price>)
+                  -> Result<()> {
+        let mut book = ConsolidatedBook::for_level(&self.client.level, stock.into());
+        for round in 0..self.max_rounds {
+            if report.remaining().is_zero() {
+                break;
+            }
+            try!(book.poll(self.client));
+            let slices = plan(&book, &direction, report.remaining(), limit);
+            if slices.is_empty() {
+                break;
+            }
+            for slice in slices {
+                let order = try!(Order::builder(&self.client.level)
+                    .venue(slice.venue)
+                    .stock(stock)
+                    .direction(direction.clone())
+                    .price(slice.price)
+                    .qty(slice.qty)
+                    .order_type(OrderType::ImmediateOrCancel)
+                    .build());
+                let res = try!(self.client.order(&order));
+                report.filled += res.total_filled;
+                report.children.push(res);
+            }
+            debug!(stock = stock,
+                   round = round,
+                   filled = report.filled.0,
+                   remaining = report.remaining().0,
+                   "routed");
+        }
+        if let (true, Some(price)) = (self.rest_remainder, limit) {
+            if !report.remaining().is_zero() {
+                let order = try!(Order::builder(&self.client.level)
+                    .venue(self.resting_venue(&book, &direction))
+                    .stock(stock)
+                    .direction(direction)
+                    .price(price)
+                    .qty(report.remaining())
+                    .build());
+                report.resting = Some(try!(self.client.order(&order)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Where the remainder should wait: with the best price on our own
+    /// side, or the level's first venue if nobody is quoting.
+    fn resting_venue(&self, book: &ConsolidatedBook, direction: &OrderDirection) -> Venue {
+        let best = match *direction {
+            OrderDirection::Buy => &book.nbbo().bid_venues,
+            OrderDirection::Sell => &book.nbbo().ask_venues,
+        };
+        best.first()
+            .or_else(|| self.client.level.venues.first())
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use client::LevelClient;
+    use consolidated::ConsolidatedBook;
+    use data::{BidAsk, OrderDirection, OrderType};
+    use endpoint::ApiProfile;
+    use test_support::{TestExchange, book, level, test_level};
+    use types::{Price, Qty};
+
+    fn ask(price: u64, qty: u64) -> BidAsk {
+        level(price, qty, false)
+    }
+
+    #[test]
+    fn test_plan() {
+        let mut c = ConsolidatedBook::for_level(&test_level(&["AEX", "BEX"]), "FOOBAR".into());
+        c.update_orderbook(&book("AEX", vec![], vec![ask(5010, 10), ask(5030, 50)]));
+        c.update_orderbook(&book("BEX", vec![], vec![ask(5000, 5), ask(5020, 5)]));
+        let slices = plan(&c, &OrderDirection::Buy, Qty(25), Some(Price(5030)));
+        assert_eq!(vec![Slice {
+                            venue: "BEX".into(),
+                            price: Price(5020),
+                            qty: Qty(10),
+                        },
+                        Slice {
+                            venue: "AEX".into(),
+                            price: Price(5030),
+                            qty: Qty(15),
+                        }],
+                   slices);
+        let capped = plan(&c, &OrderDirection::Buy, Qty(25), Some(Price(5015)));
+        assert_eq!(Qty(15), capped.iter().map(|s| s.qty).sum());
+        assert!(plan(&c, &OrderDirection::Sell, Qty(25), None).is_empty());
+    }
+
+    #[test]
+    fn test_route() {
+        let exchange = TestExchange::default();
+        exchange.books.borrow_mut().insert("AEX".to_owned(), vec![ask(5010, 10)]);
+        exchange.books.borrow_mut().insert("BEX".to_owned(), vec![ask(5000, 5), ask(5050, 100)]);
+        let client = LevelClient::new(exchange.clone(),
+                                      test_level(&["AEX", "BEX"]),
+                                      "http://localhost:8000",
+                                      ApiProfile::default());
+        let report = SmartRouter::new(&client)
+            .route(OrderDirection::Buy, "FOOBAR", Qty(20), Some(Price(5020)))
+            .unwrap();
+        assert_eq!(Qty(15), report.filled);
+        assert_eq!(Qty(5), report.remaining());
+        assert_eq!(2, report.children.len());
+        assert!(report.children
+            .iter()
+            .all(|c| matches!(c.order_type, OrderType::ImmediateOrCancel)));
+        assert_eq!(5000 * 5 + 5010 * 10, report.notional());
+        assert!((report.average_price().unwrap() - 5006.67).abs() < 0.01);
+        assert!(report.resting.is_none());
+
+        // Nothing left below the limit, so it all rests on the first venue.
+        let report = SmartRouter::new(&client)
+            .rest_remainder(true)
+            .route(OrderDirection::Buy, "FOOBAR", Qty(20), Some(Price(5020)))
+            .unwrap();
+        assert!(report.children.is_empty());
+        let resting = report.resting.unwrap();
+        assert_eq!("AEX", resting.venue.as_str());
+        assert_eq!(Qty(20), resting.original_qty);
+    }
+
+    #[test]
+    fn test_route_error_keeps_fills() {
+        let exchange = TestExchange::default();
+        exchange.books.borrow_mut().insert("AEX".to_owned(), vec![ask(5010, 10)]);
+        exchange.books.borrow_mut().insert("BEX".to_owned(), vec![ask(5000, 5)]);
+        let client = LevelClient::new(exchange.clone(),
+                                      test_level(&["AEX", "BEX"]),
+                                      "http://localhost:8000",
+                                      ApiProfile::default());
+        // BEX's slice goes out first and fills, then AEX's fails.
+        exchange.fail_next.borrow_mut().push("AEX".to_owned());
+        let report = SmartRouter::new(&client)
+            .route(OrderDirection::Buy, "FOOBAR", Qty(15), None)
+            .unwrap();
+        assert_eq!(Qty(5), report.filled);
+        assert_eq!(1, report.children.len());
+        assert!(report.error.is_some());
+
+        // Nothing sent, so nothing to report.
+        exchange.fail_next.borrow_mut().push("AEX".to_owned());
+        assert!(SmartRouter::new(&client)
+            .route(OrderDirection::Buy, "FOOBAR", Qty(10), None)
+            .is_err());
+    }
+}
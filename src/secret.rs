@@ -0,0 +1,122 @@
+use error::{Error, Result};
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use zeroize::Zeroize;
+
+/// A string that shouldn't end up in logs, like an api key.
+///
+/// `Debug` and `Display` never show the value, and the memory holding it
+/// is zeroed when it's dropped. Use `expose` to get at the value when it
+/// actually has to be sent somewhere.
+#[derive(Clone, PartialEq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: String) -> Secret {
+        Secret(value)
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// Read a secret from a file, trimming surrounding whitespace.
+    ///
+    /// # Errors
+    ///
+    /// Errors out when:
+    ///  the file can't be read
+    ///  other users can read or write the file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Secret> {
+        let path = path.as_ref();
+        let mut f = try!(File::open(path));
+        try!(check_permissions(path, &f));
+        let mut buf = String::new();
+        let res = f.read_to_string(&mut buf);
+        let secret = Secret(buf.trim().to_owned());
+        buf.zeroize();
+        try!(res);
+        Ok(secret)
+    }
+}
+
+/// Refuse a file holding secrets that other users can read or write.
+#[cfg(unix)]
+pub fn check_permissions(path: &Path, f: &File) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = try!(f.metadata()).permissions().mode();
+    if mode & 0o007 != 0 {
+        return Err(Error::Config(format!("{} can be accessed by other users, chmod 600 it",
+                                         path.display())));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn check_permissions(_: &Path, _: &File) -> Result<()> {
+    Ok(())
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Secret([REDACTED])")
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[REDACTED]")
+    }
+}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Secret {
+        Secret(value)
+    }
+}
+
+impl<'a> From<&'a str> for Secret {
+    fn from(value: &'a str) -> Secret {
+        Secret(value.to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+
+    #[test]
+    fn test_redacted() {
+        let s = Secret::from("hunter2");
+        assert!(!format!("{:?}", s).contains("hunter2"));
+        assert!(!format!("{}", s).contains("hunter2"));
+        assert_eq!("hunter2", s.expose());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_from_file_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = env::temp_dir().join(format!("michromer-secret-test-{}", ::std::process::id()));
+        File::create(&path).unwrap().write_all(b"hunter2\n").unwrap();
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(Secret::from_file(&path).is_err());
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        let res = Secret::from_file(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!("hunter2", res.unwrap().expose());
+    }
+}
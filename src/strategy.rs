@@ -0,0 +1,74 @@
+use data::{Order, OrderResponse, OrderbookResponse, QuoteResponse};
+use feed::FeedEvent;
+use flash::LevelHint;
+use std::vec;
+use types::{OrderId, Symbol, Venue};
+
+/// Something a strategy wants done in response to market data.
+#[derive(Debug, Clone)]
+pub enum Action {
+    Place(Order),
+    Cancel { venue: Venue, stock: Symbol, id: OrderId },
+}
+
+/// Collects the actions a strategy emits from inside one of its hooks.
+///
+/// Whatever is driving the strategy (a live level or the backtester)
+/// drains these after each hook returns and carries them out.
+#[derive(Debug, Clone, Default)]
+pub struct Actions {
+    actions: Vec<Action>,
+}
+
+impl Actions {
+    pub fn new() -> Actions {
+        Actions { actions: Vec::new() }
+    }
+
+    /// Ask for a new order to be sent.
+    pub fn place(&mut self, o: Order) {
+        self.actions.push(Action::Place(o));
+    }
+
+    /// Ask for an open order to be canceled.
+    pub fn cancel(&mut self, venue: &str, stock: &str, id: OrderId) {
+        self.actions.push(Action::Cancel {
+            venue: venue.into(),
+            stock: stock.into(),
+            id: id,
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    /// Take every queued action, leaving this empty.
+    pub fn drain<'a>(&'a mut self) -> vec::Drain<'a, Action> {
+        self.actions.drain(..)
+    }
+}
+
+/// Hooks for trading logic.
+///
+/// Every hook has an empty default so a strategy only needs to
+/// implement the events it cares about.
+#[allow(unused_variables)]
+pub trait Strategy {
+    /// Called with each new quote for a stock.
+    fn on_quote(&mut self, quote: &QuoteResponse, actions: &mut Actions) {}
+
+    /// Called with each new copy of a venue's order book.
+    fn on_orderbook(&mut self, book: &OrderbookResponse, actions: &mut Actions) {}
+
+    /// Called whenever one of our orders is accepted, filled or canceled.
+    fn on_order_update(&mut self, order: &OrderResponse, actions: &mut Actions) {}
+
+    /// Called with each new hint from the game master's flash messages,
+    /// as found by a `flash::FlashWatcher`.
+    fn on_level_hint(&mut self, hint: &LevelHint, actions: &mut Actions) {}
+
+    /// Called when a `feed::FeedMonitor` finds a quote feed stale,
+    /// crossed or locked, or back to normal.
+    fn on_feed_event(&mut self, event: &FeedEvent, actions: &mut Actions) {}
+}
@@ -0,0 +1,498 @@
+//! Look for insider trading in execution feeds.
+//!
+//! `making_amends` asks which account is trading on information nobody else
+//! has. `Surveillance` takes every account's `Execution`s, rebuilds each
+//! account's position and P&L as the day goes, and scores accounts on how
+//! suspicious their trading looks:
+//!
+//!  - win rate: how often the price moved their way within `horizon`
+//!  - foresight: how far it moved their way, in cents a share
+//!  - concentration: their share of all the profit made
+//!
+//! Each is compared across accounts and added up, so the score says how
+//! far an account stands out from the rest.
+
+use chrono::{DateTime, Duration, UTC};
+use data::{Execution, OrderDirection};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
+use types::{Account, OrderId, Price, Qty, Symbol, Venue};
+
+/// One side of an execution, from the account's point of view.
+#[derive(Serialize, Debug, Clone)]
+pub struct Trade {
+    pub at: DateTime<UTC>,
+    pub venue: Venue,
+    pub symbol: Symbol,
+    pub direction: OrderDirection,
+    pub price: Price,
+    pub qty: Qty,
+}
+
+impl Trade {
+    /// Shares bought, negative when sold.
+    fn signed_qty(&self) -> i64 {
+        match self.direction {
+            OrderDirection::Buy => self.qty.0 as i64,
+            OrderDirection::Sell => -(self.qty.0 as i64),
+        }
+    }
+}
+
+/// An account's standing right after one of its trades.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub at: DateTime<UTC>,
+    /// Shares held across every symbol, negative when short.
+    pub position: i64,
+    pub cash: i64,
+    /// Cash plus every position marked at its last trade price.
+    pub pnl: i64,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Ledger {
+    positions: HashMap<(Venue, Symbol), i64>,
+    cash: i64,
+    trades: Vec<Trade>,
+    history: Vec<Snapshot>,
+}
+
+/// How one account's trading looks.
+#[derive(Serialize, Debug, Clone)]
+pub struct AccountScore {
+    pub account: Account,
+    pub trades: usize,
+    pub volume: Qty,
+    pub position: i64,
+    pub pnl: i64,
+    /// The share of trades where the price had moved the account's way
+    /// `horizon` later. `None` until a trade has been followed that long.
+    pub win_rate: Option<f64>,
+    /// How far the price moved the account's way `horizon` after its
+    /// trades, in cents a share, weighted by size.
+    pub foresight: Option<f64>,
+    /// The account's share of the profit made by every account in profit.
+    pub concentration: f64,
+    /// The sum of how many standard deviations the account sits above the
+    /// others on each measure. Higher is more suspicious.
+    pub score: f64,
+}
+
+/// A trade that paid off, kept as evidence.
+#[derive(Serialize, Debug, Clone)]
+pub struct Evidence {
+    pub trade: Trade,
+    /// The price `horizon` after the trade.
+    pub price_after: Price,
+    /// Cents made on the whole trade by that time.
+    pub gain: i64,
+}
+
+/// One account in a `Report`.
+#[derive(Serialize, Debug, Clone)]
+pub struct AccountReport {
+    pub score: AccountScore,
+    pub history: Vec<Snapshot>,
+    /// The account's best trades, most profitable first.
+    pub evidence: Vec<Evidence>,
+}
+
+/// The most suspicious accounts and the trades behind their scores.
+/// Serializes to json, or `render` it for reading.
+#[derive(Serialize, Debug, Clone)]
+pub struct Report {
+    pub horizon_seconds: i64,
+    pub accounts: Vec<AccountReport>,
+}
+
+impl Report {
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for (rank, a) in self.accounts.iter().enumerate() {
+            let s = &a.score;
+            let _ = writeln!(out,
+                             "{}. {}  score {:.2}  pnl {}  position {}  trades {}  win rate {}  \
+                              foresight {}  concentration {:.0}%",
+                             rank + 1,
+                             s.account,
+                             s.score,
+                             cents(s.pnl),
+                             s.position,
+                             s.trades,
+                             s.win_rate.map_or("-".to_owned(), |w| format!("{:.0}%", w * 100.0)),
+                             s.foresight.map_or("-".to_owned(), |f| format!("{:.1}c", f)),
+                             s.concentration * 100.0);
+            for e in &a.evidence {
+                let _ = writeln!(out,
+                                 "    {} {} {:?} {} {} @ {} -> {} within {}s, {}",
+                                 e.trade.at.to_rfc3339(),
+                                 e.trade.venue,
+                                 e.trade.direction,
+                                 e.trade.qty,
+                                 e.trade.symbol,
+                                 e.trade.price,
+                                 e.price_after,
+                                 self.horizon_seconds,
+                                 cents(e.gain));
+            }
+        }
+        out
+    }
+}
+
+fn cents(c: i64) -> String {
+    let sign = if c < 0 { "-" } else { "" };
+    format!("{}${}.{:02}", sign, c.abs() / 100, c.abs() % 100)
+}
+
+/// A trade's standing and incoming order ids and when it happened.
+type TradeIds = (OrderId, OrderId, DateTime<UTC>);
+
+/// Prints in time order.
+type Series = Vec<(DateTime<UTC>, Price)>;
+
+/// The last print on each venue for each symbol.
+type Marks = HashMap<(Venue, Symbol), Price>;
+
+/// Rebuilds every account's trading from execution feeds and scores it.
+#[derive(Debug, Clone)]
+pub struct Surveillance {
+    horizon: Duration,
+    ledgers: BTreeMap<Account, Ledger>,
+    /// Each venue's prints for each symbol, in time order.
+    prints: HashMap<(Venue, Symbol), Series>,
+    /// Order ids are only unique within a venue, so these keys include it.
+    seen: HashSet<(Account, Venue, OrderId, TradeIds)>,
+    printed: HashSet<(Venue, TradeIds)>,
+}
+
+impl Surveillance {
+    /// Judge each trade by where the price is `horizon` after it.
+    pub fn new(horizon: Duration) -> Surveillance {
+        Surveillance {
+            horizon: horizon,
+            ledgers: BTreeMap::new(),
+            prints: HashMap::new(),
+            seen: HashSet::new(),
+            printed: HashSet::new(),
+        }
+    }
+
+    /// Add an execution from any account's feed.
+    ///
+    /// Both sides of a trade can be added, each from its own account's
+    /// feed, without the print counting twice, and an execution seen again
+    /// after a reconnect is ignored.
+    pub fn record(&mut self, e: &Execution) {
+        let direction = match e.order.direction {
+            Some(ref d) if e.ok => d.clone(),
+            _ => return,
+        };
+        let ids = (e.standing_id, e.incoming_id, e.filled_at);
+        if !self.seen.insert((e.account.clone(), e.venue.clone(), e.order.id, ids)) {
+            return;
+        }
+        if self.printed.insert((e.venue.clone(), ids)) {
+            let series = self.prints.entry((e.venue.clone(), e.symbol.clone())).or_default();
+            let i = match series.binary_search_by(|p| p.0.cmp(&e.filled_at)) {
+                Ok(i) | Err(i) => i,
+            };
+            series.insert(i, (e.filled_at, e.price));
+        }
+        let trade = Trade {
+            at: e.filled_at,
+            venue: e.venue.clone(),
+            symbol: e.symbol.clone(),
+            direction: direction,
+            price: e.price,
+            qty: e.filled,
+        };
+        let marks = self.marks();
+        let ledger = self.ledgers.entry(e.account.clone()).or_default();
+        *ledger.positions.entry((trade.venue.clone(), trade.symbol.clone())).or_insert(0) +=
+            trade.signed_qty();
+        ledger.cash -= trade.signed_qty() * trade.price.0 as i64;
+        ledger.trades.push(trade);
+        let snapshot = Snapshot {
+            at: e.filled_at,
+            position: ledger.positions.values().sum(),
+            cash: ledger.cash,
+            pnl: pnl(ledger, &marks),
+        };
+        ledger.history.push(snapshot);
+    }
+
+    /// Every account seen so far.
+    pub fn accounts(&self) -> Vec<&Account> {
+        self.ledgers.keys().collect()
+    }
+
+    /// An account's position and P&L after each of its trades.
+    pub fn history(&self, account: &str) -> &[Snapshot] {
+        self.ledgers.get(account).map_or(&[][..], |l| &l.history[..])
+    }
+
+    /// An account's P&L with every position marked at the last price.
+    pub fn pnl(&self, account: &str) -> Option<i64> {
+        let marks = self.marks();
+        self.ledgers.get(account).map(|l| pnl(l, &marks))
+    }
+
+    /// Every account, most suspicious first.
+    pub fn scores(&self) -> Vec<AccountScore> {
+        let marks = self.marks();
+        let mut scores: Vec<AccountScore> = self.ledgers
+            .iter()
+            .map(|(account, ledger)| self.score(account, ledger, &marks))
+            .collect();
+        let total_profit: i64 = scores.iter().map(|s| s.pnl.max(0)).sum();
+        for s in &mut scores {
+            if total_profit > 0 {
+                s.concentration = s.pnl.max(0) as f64 / total_profit as f64;
+            }
+        }
+        let win = z_scores(&scores.iter().map(|s| s.win_rate).collect::<Vec<_>>());
+        let ahead = z_scores(&scores.iter().map(|s| s.foresight).collect::<Vec<_>>());
+        let share = z_scores(&scores.iter().map(|s| Some(s.concentration)).collect::<Vec<_>>());
+        for (i, s) in scores.iter_mut().enumerate() {
+            s.score = win[i] + ahead[i] + share[i];
+        }
+        scores.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(::std::cmp::Ordering::Equal));
+        scores
+    }
+
+    /// The `top` most suspicious accounts, each with its history and its
+    /// `evidence` most profitable trades.
+    pub fn report(&self, top: usize, evidence: usize) -> Report {
+        let accounts = self.scores()
+            .into_iter()
+            .take(top)
+            .map(|score| {
+                let ledger = &self.ledgers[&score.account];
+                let mut found: Vec<Evidence> = ledger.trades
+                    .iter()
+                    .filter_map(|t| {
+                        self.price_after(t).map(|after| {
+                            Evidence {
+                                trade: t.clone(),
+                                price_after: after,
+                                gain: (after.0 as i64 - t.price.0 as i64) * t.signed_qty(),
+                            }
+                        })
+                    })
+                    .filter(|e| e.gain > 0)
+                    .collect();
+                found.sort_by_key(|e| ::std::cmp::Reverse(e.gain));
+                found.truncate(evidence);
+                AccountReport {
+                    score: score,
+                    history: ledger.history.clone(),
+                    evidence: found,
+                }
+            })
+            .collect();
+        Report {
+            horizon_seconds: self.horizon.num_seconds(),
+            accounts: accounts,
+        }
+    }
+
+    fn score(&self, account: &Account, ledger: &Ledger, marks: &Marks) -> AccountScore {
+        let mut judged = 0;
+        let mut wins = 0;
+        let mut moved = 0i64;
+        let mut judged_qty = 0i64;
+        for t in &ledger.trades {
+            if let Some(after) = self.price_after(t) {
+                let gain = (after.0 as i64 - t.price.0 as i64) * t.signed_qty().signum();
+                judged += 1;
+                if gain > 0 {
+                    wins += 1;
+                }
+                moved += gain * t.qty.0 as i64;
+                judged_qty += t.qty.0 as i64;
+            }
+        }
+        AccountScore {
+            account: account.clone(),
+            trades: ledger.trades.len(),
+            volume: ledger.trades.iter().map(|t| t.qty).sum(),
+            position: ledger.positions.values().sum(),
+            pnl: pnl(ledger, marks),
+            win_rate: if judged > 0 { Some(wins as f64 / judged as f64) } else { None },
+            foresight: if judged_qty > 0 { Some(moved as f64 / judged_qty as f64) } else { None },
+            concentration: 0.0,
+            score: 0.0,
+        }
+    }
+
+    /// The first print at least `horizon` after a trade.
+    fn price_after(&self, t: &Trade) -> Option<Price> {
+        let series = match self.prints.get(&(t.venue.clone(), t.symbol.clone())) {
+            Some(s) => s,
+            None => return None,
+        };
+        let when = t.at + self.horizon;
+        let i = match series.binary_search_by(|p| p.0.cmp(&when)) {
+            Ok(i) | Err(i) => i,
+        };
+        series.get(i).map(|p| p.1)
+    }
+
+    fn marks(&self) -> Marks {
+        self.prints
+            .iter()
+            .filter_map(|(s, series)| series.last().map(|p| (s.clone(), p.1)))
+            .collect()
+    }
+}
+
+fn pnl(ledger: &Ledger, marks: &Marks) -> i64 {
+    ledger.cash +
+    ledger.positions
+        .iter()
+        .map(|(s, &n)| n * marks.get(s).map_or(0, |p| p.0 as i64))
+        .sum::<i64>()
+}
+
+/// How many standard deviations each value is from the mean of the ones
+/// present. Missing values, and everything when there's no spread, get 0.
+fn z_scores(values: &[Option<f64>]) -> Vec<f64> {
+    let present: Vec<f64> = values.iter().filter_map(|v| *v).collect();
+    if present.len() < 2 {
+        return vec![0.0; values.len()];
+    }
+    let n = present.len() as f64;
+    let mean = present.iter().sum::<f64>() / n;
+    let sd = (present.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n).sqrt();
+    values.iter()
+        .map(|v| match *v {
+            Some(v) if sd > 0.0 => (v - mean) / sd,
+            _ => 0.0,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Duration, UTC};
+    use data::{Execution, OrderDirection, OrderResponse, OrderType};
+    use types::{OrderId, Price, Qty};
+
+    fn at(second: u32) -> DateTime<UTC> {
+        format!("2016-06-02T16:20:{:02}Z", second).parse().unwrap()
+    }
+
+    fn execution(account: &str,
+                 direction: OrderDirection,
+                 price: u64,
+                 qty: u64,
+                 second: u32,
+                 ids: (u64, u64))
+                 -> Execution {
+        // Whoever bought owns the standing order, the seller the incoming one.
+        let id = match direction {
+            OrderDirection::Buy => ids.0,
+            OrderDirection::Sell => ids.1,
+        };
+        Execution {
+            ok: true,
+            account: account.into(),
+            venue: "TESTEX".into(),
+            symbol: "FOOBAR".into(),
+            order: OrderResponse {
+                ok: true,
+                symbol: "FOOBAR".into(),
+                venue: "TESTEX".into(),
+                direction: Some(direction),
+                original_qty: Qty(qty),
+                qty: Qty(0),
+                price: Price(price),
+                order_type: OrderType::Limit,
+                id: OrderId(id),
+                account: account.into(),
+                ts: at(second),
+                fills: vec![],
+                total_filled: Qty(qty),
+                open: false,
+            },
+            standing_id: OrderId(ids.0),
+            incoming_id: OrderId(ids.1),
+            price: Price(price),
+            filled: Qty(qty),
+            filled_at: at(second),
+            standing_complete: true,
+            incoming_complete: true,
+        }
+    }
+
+    /// The insider buys from the noise trader right before every rise and
+    /// sells right before every drop.
+    fn session() -> Surveillance {
+        let mut s = Surveillance::new(Duration::seconds(5));
+        let prices = [5000, 5100, 5200, 5100, 5000, 4900, 5000];
+        for (i, &p) in prices.iter().enumerate() {
+            let second = i as u32 * 5;
+            let rising = prices.get(i + 1).map_or(true, |&next| next > p);
+            let (insider, noise) = if rising {
+                (OrderDirection::Buy, OrderDirection::Sell)
+            } else {
+                (OrderDirection::Sell, OrderDirection::Buy)
+            };
+            let ids = (i as u64 * 2, i as u64 * 2 + 1);
+            s.record(&execution("INSIDER", insider, p, 10, second, ids));
+            s.record(&execution("NOISE", noise, p, 10, second, ids));
+            // A market maker trading with itself at the same prices.
+            let mm = (100 + i as u64 * 2, 101 + i as u64 * 2);
+            s.record(&execution("MM", OrderDirection::Buy, p, 5, second, mm));
+            s.record(&execution("MM", OrderDirection::Sell, p, 5, second, mm));
+        }
+        s
+    }
+
+    #[test]
+    fn test_positions_and_pnl() {
+        let mut s = session();
+        assert_eq!(vec!["INSIDER", "MM", "NOISE"],
+                   s.accounts().iter().map(|a| a.as_str()).collect::<Vec<_>>());
+        let insider = s.history("INSIDER");
+        assert_eq!(7, insider.len());
+        assert_eq!(10, insider[0].position);
+        assert_eq!(-50000, insider[0].cash);
+        assert_eq!(Some(0), s.pnl("MM"));
+        assert_eq!(Some(-s.pnl("INSIDER").unwrap()), s.pnl("NOISE"));
+
+        // Replays don't count twice.
+        let before = s.history("INSIDER").len();
+        s.record(&execution("INSIDER", OrderDirection::Buy, 5000, 10, 0, (0, 1)));
+        assert_eq!(before, s.history("INSIDER").len());
+
+        // The same ids on another venue are another trade, with its own prints.
+        let mut other = execution("INSIDER", OrderDirection::Buy, 6000, 10, 0, (0, 1));
+        other.venue = "OTHEREX".into();
+        s.record(&other);
+        assert_eq!(before + 1, s.history("INSIDER").len());
+        assert_eq!(vec![(at(0), Price(6000))],
+                   s.prints[&("OTHEREX".into(), "FOOBAR".into())]);
+        assert!(s.prints[&("TESTEX".into(), "FOOBAR".into())].iter().all(|p| p.1 != Price(6000)));
+    }
+
+    #[test]
+    fn test_ranks_insider_first() {
+        let s = session();
+        let scores = s.scores();
+        assert_eq!("INSIDER", scores[0].account.as_str());
+        assert_eq!(Some(1.0), scores[0].win_rate);
+        assert_eq!(Some(100.0), scores[0].foresight);
+        assert_eq!(1.0, scores[0].concentration);
+        assert!(scores[0].score > scores[1].score);
+
+        let report = s.report(1, 2);
+        assert_eq!(1, report.accounts.len());
+        assert_eq!(2, report.accounts[0].evidence.len());
+        assert_eq!(1000, report.accounts[0].evidence[0].gain);
+        assert!(report.render().starts_with("1. INSIDER"));
+    }
+}
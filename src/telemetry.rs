@@ -0,0 +1,56 @@
+//! Send the client's `tracing` spans to an OpenTelemetry collector.
+
+use error::{Error, Result};
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+/// Where a collector running on this machine takes OTLP over http.
+pub static LOCAL_COLLECTOR: &'static str = "http://127.0.0.1:4318/v1/traces";
+
+/// Keeps the exporter running. Dropping it flushes any spans that haven't
+/// been sent yet.
+#[derive(Debug)]
+pub struct Telemetry {
+    provider: SdkTracerProvider,
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        let _ = self.provider.shutdown();
+    }
+}
+
+/// Install a global subscriber that exports spans over OTLP/http to
+/// `endpoint`, `LOCAL_COLLECTOR` if none is given.
+///
+/// `RUST_LOG` picks which spans and events are sent, defaulting to `info`.
+///
+/// # Errors
+///
+/// Errors out when:
+///  the exporter can't be built
+///  a global subscriber is already installed
+pub fn init(service_name: &str, endpoint: Option<&str>) -> Result<Telemetry> {
+    let exporter = try!(SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint.unwrap_or(LOCAL_COLLECTOR))
+        .build()
+        .map_err(|e| Error::Config(format!("can't build the span exporter: {}", e))));
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name(service_name.to_owned()).build())
+        .build();
+    let tracer = provider.tracer("michromer");
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    try!(tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_opentelemetry::layer().with_tracer(tracer))
+        .try_init()
+        .map_err(|e| Error::Config(format!("can't install the tracing subscriber: {}", e))));
+    Ok(Telemetry { provider: provider })
+}
@@ -0,0 +1,134 @@
+//! Fixtures shared by the unit tests.
+
+use chrono::UTC;
+use data::{BidAsk, Fill, Level, Order, OrderResponse, OrderbookResponse};
+use error::{Error, Result};
+use http::HttpClient;
+use serde_json;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io;
+use std::rc::Rc;
+use types::{OrderId, Price, Qty};
+
+/// One price level of a book.
+pub fn level(price: u64, qty: u64, is_buy: bool) -> BidAsk {
+    BidAsk {
+        price: Price(price),
+        qty: Qty(qty),
+        is_buy: is_buy,
+    }
+}
+
+/// A FOOBAR book at `venue`.
+pub fn book(venue: &str, bids: Vec<BidAsk>, asks: Vec<BidAsk>) -> OrderbookResponse {
+    OrderbookResponse {
+        ok: true,
+        venue: venue.into(),
+        symbol: "FOOBAR".into(),
+        bids: Some(bids),
+        asks: Some(asks),
+        ts: "2015-12-04T09:02:16.680986205Z".parse().unwrap(),
+    }
+}
+
+/// A level trading FOOBAR on `venues`.
+pub fn test_level(venues: &[&str]) -> Level {
+    Level {
+        ok: true,
+        instance_id: 1,
+        account: "EXB123456".into(),
+        instructions: HashMap::new(),
+        tickers: vec!["FOOBAR".into()],
+        venues: venues.iter().map(|&v| v.into()).collect(),
+    }
+}
+
+/// The venue's answer to `o`, closed after these fills.
+pub fn response(o: Order, fills: Vec<Fill>) -> OrderResponse {
+    let filled = fills.iter().map(|f| f.qty).sum();
+    OrderResponse {
+        ok: true,
+        symbol: o.stock,
+        venue: o.venue,
+        direction: Some(o.direction),
+        original_qty: o.qty,
+        qty: o.qty - filled,
+        price: o.price,
+        order_type: o.order_type,
+        id: OrderId(1),
+        account: o.account,
+        ts: UTC::now(),
+        fills: fills,
+        total_filled: filled,
+        open: false,
+    }
+}
+
+/// Venues that fill orders and record every order sent.
+///
+/// A venue with a book only has asks, and fills buys against them. Any
+/// other venue fills each order up to its `fill_up_to` shares, or in full,
+/// at the order's price, and market orders at $49.90. The next order sent
+/// to a venue in `fail_next` errors out instead, once.
+#[derive(Debug, Clone, Default)]
+pub struct TestExchange {
+    pub books: Rc<RefCell<HashMap<String, Vec<BidAsk>>>>,
+    pub fill_up_to: Rc<RefCell<HashMap<String, u64>>>,
+    pub fail_next: Rc<RefCell<Vec<String>>>,
+    pub sent: Rc<RefCell<Vec<Order>>>,
+}
+
+impl HttpClient for TestExchange {
+    fn get(&self, url: &str) -> Result<String> {
+        let venue = url.split('/').nth(6).unwrap().to_owned();
+        let asks = self.books.borrow().get(&venue).cloned().unwrap_or_default();
+        Ok(serde_json::to_string(&book(&venue, vec![], asks)).unwrap())
+    }
+    fn post(&self, _url: &str, body: Option<&str>) -> Result<String> {
+        let o: Order = serde_json::from_str(body.unwrap()).unwrap();
+        self.sent.borrow_mut().push(o.clone());
+        let failing = self.fail_next.borrow().iter().position(|v| v == o.venue.as_str());
+        if let Some(i) = failing {
+            self.fail_next.borrow_mut().remove(i);
+            return Err(Error::IO(io::Error::new(io::ErrorKind::ConnectionRefused, "venue down")));
+        }
+        let mut books = self.books.borrow_mut();
+        let asks = match books.get_mut(o.venue.as_str()) {
+            Some(asks) => asks,
+            None => {
+                let most = self.fill_up_to.borrow().get(o.venue.as_str()).cloned();
+                let filled = Qty(most.map_or(o.qty.0, |m| m.min(o.qty.0)));
+                let fills = if filled.is_zero() {
+                    vec![]
+                } else {
+                    vec![Fill {
+                             price: if o.price.0 == 0 { Price(4990) } else { o.price },
+                             qty: filled,
+                             ts: UTC::now(),
+                         }]
+                };
+                return Ok(serde_json::to_string(&response(o, fills)).unwrap());
+            }
+        };
+        let mut fills = Vec::new();
+        let mut left = o.qty;
+        for a in asks.iter_mut().filter(|a| a.price <= o.price) {
+            let take = if a.qty < left { a.qty } else { left };
+            a.qty -= take;
+            left -= take;
+            if !take.is_zero() {
+                fills.push(Fill {
+                    price: a.price,
+                    qty: take,
+                    ts: UTC::now(),
+                });
+            }
+        }
+        asks.retain(|a| !a.qty.is_zero());
+        Ok(serde_json::to_string(&response(o, fills)).unwrap())
+    }
+    fn delete(&self, url: &str) -> Result<String> {
+        panic!("unexpected delete {}", url)
+    }
+}
@@ -0,0 +1,195 @@
+//! The pieces `AuthHttpClient` is built from: a connector that can give up
+//! on slow connects, and TLS for hyper using rustls.
+
+use error::{Error, Result};
+use hyper;
+use hyper::net::{HttpConnector, HttpStream, NetworkConnector, NetworkStream, SslClient};
+use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+use rustls::crypto::ring;
+use rustls::pki_types::{CertificateDer, ServerName};
+use rustls::pki_types::pem::PemObject;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use webpki_roots;
+
+/// Opens plain tcp connections, like hyper's `HttpConnector`, but can give
+/// up on a connect that takes too long.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimeoutConnector {
+    pub connect_timeout: Option<Duration>,
+}
+
+impl NetworkConnector for TimeoutConnector {
+    type Stream = HttpStream;
+
+    fn connect(&self, host: &str, port: u16, scheme: &str) -> hyper::Result<HttpStream> {
+        let timeout = match self.connect_timeout {
+            Some(t) => t,
+            None => return HttpConnector.connect(host, port, scheme),
+        };
+        if scheme != "http" {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Invalid scheme for Http")
+                .into());
+        }
+        let mut last_err = None;
+        for addr in try!((host, port).to_socket_addrs()) {
+            match TcpStream::connect_timeout(&addr, timeout) {
+                Ok(s) => return Ok(HttpStream(s)),
+                Err(e) => last_err = Some(e),
+            }
+        }
+        Err(last_err.unwrap_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, format!("{} didn't resolve", host))
+            })
+            .into())
+    }
+}
+
+/// Wraps connections to `https` urls in TLS.
+///
+/// Servers are checked against the Mozilla root certificates, plus any
+/// extra roots given, which is how a self-signed local server is trusted.
+#[derive(Clone)]
+pub struct TlsClient {
+    config: Arc<ClientConfig>,
+}
+
+impl TlsClient {
+    /// Trust the built in roots and every certificate in the given PEM files.
+    ///
+    /// # Errors
+    ///
+    /// Errors out when:
+    ///  a file can't be read or has no certificates
+    ///  a certificate isn't valid
+    pub fn new<P: AsRef<Path>>(extra_roots: &[P]) -> Result<TlsClient> {
+        let mut roots = RootCertStore { roots: webpki_roots::TLS_SERVER_ROOTS.to_vec() };
+        for path in extra_roots {
+            let path = path.as_ref();
+            let bad = |e: &dyn fmt::Display| {
+                Error::Config(format!("can't load certificates from {}: {}", path.display(), e))
+            };
+            let certs = try!(CertificateDer::pem_file_iter(path).map_err(|e| bad(&e)));
+            let mut found = false;
+            for cert in certs {
+                let cert = try!(cert.map_err(|e| bad(&e)));
+                try!(roots.add(cert).map_err(|e| bad(&e)));
+                found = true;
+            }
+            if !found {
+                return Err(bad(&"no certificates in file"));
+            }
+        }
+        let config = try!(ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .map_err(|e| Error::Config(format!("can't set up tls: {}", e))))
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        Ok(TlsClient { config: Arc::new(config) })
+    }
+}
+
+impl fmt::Debug for TlsClient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TlsClient")
+    }
+}
+
+impl SslClient<HttpStream> for TlsClient {
+    type Stream = TlsStream;
+
+    fn wrap_client(&self, stream: HttpStream, host: &str) -> hyper::Result<TlsStream> {
+        let name = try!(ServerName::try_from(host.to_owned())
+            .map_err(|e| hyper::Error::Ssl(Box::new(e))));
+        let conn = try!(ClientConnection::new(self.config.clone(), name)
+            .map_err(|e| hyper::Error::Ssl(Box::new(e))));
+        let mut tls = StreamOwned::new(conn, stream);
+        // Finish the handshake here so a bad certificate fails the connect
+        // rather than the first read.
+        while tls.conn.is_handshaking() {
+            try!(tls.conn.complete_io(&mut tls.sock));
+        }
+        Ok(TlsStream(Arc::new(Mutex::new(tls))))
+    }
+}
+
+/// A TLS connection made by `TlsClient`.
+#[derive(Clone)]
+pub struct TlsStream(Arc<Mutex<StreamOwned<ClientConnection, HttpStream>>>);
+
+impl TlsStream {
+    fn lock(&self) -> MutexGuard<'_, StreamOwned<ClientConnection, HttpStream>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl fmt::Debug for TlsStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TlsStream")
+    }
+}
+
+impl Read for TlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.lock().read(buf)
+    }
+}
+
+impl Write for TlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.lock().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.lock().flush()
+    }
+}
+
+impl NetworkStream for TlsStream {
+    fn peer_addr(&mut self) -> io::Result<SocketAddr> {
+        self.lock().sock.peer_addr()
+    }
+
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.lock().sock.set_read_timeout(dur)
+    }
+
+    fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.lock().sock.set_write_timeout(dur)
+    }
+
+    fn close(&mut self, how: Shutdown) -> io::Result<()> {
+        let mut tls = self.lock();
+        tls.conn.send_close_notify();
+        let _ = tls.flush();
+        tls.sock.close(how)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    #[test]
+    fn test_connect_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let c = TimeoutConnector { connect_timeout: Some(Duration::from_secs(5)) };
+        assert!(c.connect("127.0.0.1", port, "http").is_ok());
+        assert!(c.connect("127.0.0.1", port, "ftp").is_err());
+    }
+
+    #[test]
+    fn test_missing_roots() {
+        assert!(TlsClient::new::<PathBuf>(&[]).is_ok());
+        assert!(TlsClient::new(&["/nonexistent/michromer-ca.pem"]).is_err());
+    }
+}
@@ -0,0 +1,271 @@
+use error::Error;
+use std::borrow::Borrow;
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Deref, Sub, SubAssign};
+use std::str::FromStr;
+
+/// A price in cents. Displays and parses as dollars, e.g. `$51.23`.
+///
+/// Serializes as the bare number of cents, just like the api.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash,
+         Default)]
+pub struct Price(pub u64);
+
+/// A number of shares.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash,
+         Default)]
+pub struct Qty(pub u64);
+
+/// The id a venue gives an order.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash,
+         Default)]
+pub struct OrderId(pub u64);
+
+impl Price {
+    pub fn checked_add(self, other: Price) -> Option<Price> {
+        self.0.checked_add(other.0).map(Price)
+    }
+
+    pub fn checked_sub(self, other: Price) -> Option<Price> {
+        self.0.checked_sub(other.0).map(Price)
+    }
+
+    /// The cost in cents of buying qty shares at this price.
+    pub fn checked_mul(self, qty: Qty) -> Option<u64> {
+        self.0.checked_mul(qty.0)
+    }
+}
+
+impl Qty {
+    pub fn checked_add(self, other: Qty) -> Option<Qty> {
+        self.0.checked_add(other.0).map(Qty)
+    }
+
+    pub fn checked_sub(self, other: Qty) -> Option<Qty> {
+        self.0.checked_sub(other.0).map(Qty)
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+}
+
+/// Arithmetic that panics on overflow in every build, not just debug ones.
+macro_rules! checked_ops {
+    ($t:ident) => {
+        impl Add for $t {
+            type Output = $t;
+            fn add(self, other: $t) -> $t {
+                self.checked_add(other).expect(concat!(stringify!($t), " overflow"))
+            }
+        }
+
+        impl Sub for $t {
+            type Output = $t;
+            fn sub(self, other: $t) -> $t {
+                self.checked_sub(other).expect(concat!(stringify!($t), " underflow"))
+            }
+        }
+
+        impl AddAssign for $t {
+            fn add_assign(&mut self, other: $t) {
+                *self = *self + other;
+            }
+        }
+
+        impl SubAssign for $t {
+            fn sub_assign(&mut self, other: $t) {
+                *self = *self - other;
+            }
+        }
+    }
+}
+
+checked_ops!(Price);
+checked_ops!(Qty);
+
+impl Sum for Qty {
+    fn sum<I: Iterator<Item = Qty>>(iter: I) -> Qty {
+        iter.fold(Qty(0), Add::add)
+    }
+}
+
+impl<'a> Sum<&'a Qty> for Qty {
+    fn sum<I: Iterator<Item = &'a Qty>>(iter: I) -> Qty {
+        iter.cloned().sum()
+    }
+}
+
+impl fmt::Display for Price {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "${}.{:02}", self.0 / 100, self.0 % 100)
+    }
+}
+
+impl FromStr for Price {
+    type Err = Error;
+
+    /// Parse dollars, with or without a leading `$` and with up to two
+    /// digits of cents.
+    fn from_str(s: &str) -> Result<Price, Error> {
+        let bad = || Error::Parse(format!("{:?} isn't a price", s));
+        let trimmed = s.trim();
+        let trimmed = if trimmed.starts_with('$') {
+            &trimmed[1..]
+        } else {
+            trimmed
+        };
+        let mut parts = trimmed.splitn(2, '.');
+        let dollars = parts.next().unwrap_or("");
+        let cents = parts.next().unwrap_or("");
+        let digits = |d: &str| d.chars().all(|c| c.is_digit(10));
+        if dollars.is_empty() || !digits(dollars) || !digits(cents) || cents.len() > 2 {
+            return Err(bad());
+        }
+        let dollars: u64 = try!(dollars.parse().map_err(|_| bad()));
+        let cents: u64 = match cents.len() {
+            0 => 0,
+            1 => try!(cents.parse::<u64>().map_err(|_| bad())) * 10,
+            _ => try!(cents.parse().map_err(|_| bad())),
+        };
+        dollars.checked_mul(100)
+            .and_then(|d| d.checked_add(cents))
+            .map(Price)
+            .ok_or_else(bad)
+    }
+}
+
+impl fmt::Display for Qty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl fmt::Display for OrderId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for Qty {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Qty, Error> {
+        s.trim().parse().map(Qty).map_err(|_| Error::Parse(format!("{:?} isn't a quantity", s)))
+    }
+}
+
+impl FromStr for OrderId {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<OrderId, Error> {
+        s.trim().parse().map(OrderId).map_err(|_| Error::Parse(format!("{:?} isn't an order id", s)))
+    }
+}
+
+/// Names that are strings on the wire but shouldn't be mixed up with each other.
+macro_rules! name_type {
+    ($(#[$attr:meta])* $t:ident) => {
+        $(#[$attr])*
+        #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash,
+                 Default)]
+        pub struct $t(pub String);
+
+        impl $t {
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl Deref for $t {
+            type Target = str;
+            fn deref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl Borrow<str> for $t {
+            fn borrow(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl fmt::Display for $t {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+
+        impl<'a> From<&'a str> for $t {
+            fn from(s: &'a str) -> $t {
+                $t(s.to_owned())
+            }
+        }
+
+        impl From<String> for $t {
+            fn from(s: String) -> $t {
+                $t(s)
+            }
+        }
+
+        impl<'a> PartialEq<&'a str> for $t {
+            fn eq(&self, other: &&'a str) -> bool {
+                self.0 == *other
+            }
+        }
+    }
+}
+
+name_type!(
+    /// An exchange, e.g. `TESTEX`.
+    Venue
+);
+name_type!(
+    /// A stock ticker, e.g. `FOOBAR`.
+    Symbol
+);
+name_type!(
+    /// A trading account, e.g. `EXB123456`.
+    Account
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn test_price_display_and_parse() {
+        assert_eq!("$51.23", Price(5123).to_string());
+        assert_eq!("$0.05", Price(5).to_string());
+        assert_eq!(Price(5123), "$51.23".parse().unwrap());
+        assert_eq!(Price(5120), "51.2".parse().unwrap());
+        assert_eq!(Price(5100), "$51".parse().unwrap());
+        assert!("$51.234".parse::<Price>().is_err());
+        assert!("$".parse::<Price>().is_err());
+        assert!("-1.00".parse::<Price>().is_err());
+    }
+
+    #[test]
+    fn test_checked_arithmetic() {
+        assert_eq!(None, Qty(1).checked_sub(Qty(2)));
+        assert_eq!(Some(Qty(3)), Qty(1).checked_add(Qty(2)));
+        assert_eq!(None, Price(u64::max_value()).checked_mul(Qty(2)));
+        assert_eq!(Qty(6), vec![Qty(1), Qty(2), Qty(3)].into_iter().sum());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_qty_underflow_panics() {
+        let _ = Qty(1) - Qty(2);
+    }
+
+    #[test]
+    fn test_wire_compatible() {
+        assert_eq!("5123", serde_json::to_string(&Price(5123)).unwrap());
+        assert_eq!("\"TESTEX\"", serde_json::to_string(&Venue::from("TESTEX")).unwrap());
+        let s: Symbol = serde_json::from_str("\"FOOBAR\"").unwrap();
+        assert_eq!(s, "FOOBAR");
+        let q: Qty = serde_json::from_str("100").unwrap();
+        assert_eq!(Qty(100), q);
+    }
+}
@@ -0,0 +1,394 @@
+//! Keep an eye on the api, the venues and the level instance from a
+//! background thread.
+//!
+//! A level torn down by the game master doesn't stop a bot by itself;
+//! orders just start failing, or worse, keep going to a venue nobody is
+//! scoring. `Watchdog` polls `heart_beat`, `venue_heart_beat` and the
+//! instance status, and reports whenever one of them goes up or down.
+
+use chrono::{DateTime, UTC};
+use client::{Client, LevelClient};
+use http::HttpClient;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use types::Venue;
+
+/// One thing the watchdog checks.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Check {
+    /// The api as a whole, from `heart_beat`.
+    Api,
+    Venue(Venue),
+    /// A level instance, from the game master. It's down once the level is
+    /// done.
+    Instance(i64),
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Check::Api => write!(f, "api"),
+            Check::Venue(ref v) => write!(f, "venue {}", v),
+            Check::Instance(id) => write!(f, "instance {}", id),
+        }
+    }
+}
+
+/// A check that went up or down. The first result of each check counts as
+/// a change too.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transition {
+    pub check: Check,
+    pub up: bool,
+    /// Why it's down.
+    pub reason: Option<String>,
+    pub at: DateTime<UTC>,
+}
+
+type State = Arc<Mutex<BTreeMap<Check, bool>>>;
+type Callback = Box<dyn FnMut(&Transition) + Send>;
+
+/// Polls heartbeats and instance status, and publishes the changes to
+/// callbacks and channels.
+/// ```no_run
+/// # use michromer::client::Client;
+/// # use michromer::watchdog::Watchdog;
+/// # use std::time::Duration;
+/// let client = Client::new("key");
+/// let lc = client.start_level("first_steps").unwrap();
+/// let id = lc.level.instance_id;
+/// let mut dog = Watchdog::new(lc.clone())
+///     .instance(client, id)
+///     .interval(Duration::from_secs(2))
+///     .on_change(|t| println!("{} up: {}", t.check, t.up));
+/// let changes = dog.subscribe();
+/// let handle = dog.spawn();
+/// // ... trade, checking handle.all_up() before each order.
+/// ```
+pub struct Watchdog<T: HttpClient + Clone> {
+    level: LevelClient<T>,
+    game_master: Option<(Client<T>, i64)>,
+    venues: Vec<Venue>,
+    interval: Duration,
+    callbacks: Vec<Callback>,
+    senders: Vec<Sender<Transition>>,
+    state: State,
+}
+
+impl<T: HttpClient + Clone> Watchdog<T> {
+    /// Watch the api and every venue in the level, once a second.
+    pub fn new(level: LevelClient<T>) -> Watchdog<T> {
+        let venues = level.level.venues.clone();
+        Watchdog {
+            level: level,
+            game_master: None,
+            venues: venues,
+            interval: Duration::from_secs(1),
+            callbacks: Vec::new(),
+            senders: Vec::new(),
+            state: Arc::new(Mutex::new(BTreeMap::new())),
+        }
+    }
+
+    /// How long to wait between rounds of checks.
+    pub fn interval(mut self, interval: Duration) -> Watchdog<T> {
+        self.interval = interval;
+        self
+    }
+
+    /// Watch these venues instead of the level's.
+    pub fn venues(mut self, venues: Vec<Venue>) -> Watchdog<T> {
+        self.venues = venues;
+        self
+    }
+
+    /// Also ask the game master whether this instance is still running.
+    pub fn instance(mut self, client: Client<T>, instance_id: i64) -> Watchdog<T> {
+        self.game_master = Some((client, instance_id));
+        self
+    }
+
+    /// Call `f` with every change. It runs on the watchdog's thread.
+    pub fn on_change<F>(mut self, f: F) -> Watchdog<T>
+        where F: FnMut(&Transition) + Send + 'static
+    {
+        self.callbacks.push(Box::new(f));
+        self
+    }
+
+    /// A channel that gets every change.
+    pub fn subscribe(&mut self) -> Receiver<Transition> {
+        let (tx, rx) = mpsc::channel();
+        self.senders.push(tx);
+        rx
+    }
+
+    /// Run every check once and publish whatever changed.
+    pub fn poll(&mut self) -> Vec<Transition> {
+        let mut results = vec![(Check::Api, self.api())];
+        for venue in &self.venues {
+            results.push((Check::Venue(venue.clone()), self.venue(venue)));
+        }
+        if let Some((ref client, id)) = self.game_master {
+            results.push((Check::Instance(id), instance(client, id)));
+        }
+
+        let mut changes = Vec::new();
+        {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            for (check, result) in results {
+                let up = result.is_ok();
+                if state.insert(check.clone(), up) == Some(up) {
+                    continue;
+                }
+                changes.push(Transition {
+                    check: check,
+                    up: up,
+                    reason: result.err(),
+                    at: UTC::now(),
+                });
+            }
+        }
+        for t in &changes {
+            if t.up {
+                info!(check = %t.check, "watchdog: up");
+            } else {
+                warn!(check = %t.check, reason = ?t.reason, "watchdog: down");
+            }
+            for f in &mut self.callbacks {
+                f(t);
+            }
+            // A receiver that's gone away just stops getting them.
+            self.senders.retain(|tx| tx.send(t.clone()).is_ok());
+        }
+        changes
+    }
+
+    /// Whether a check was up last time it ran.
+    pub fn is_up(&self, check: &Check) -> Option<bool> {
+        is_up(&self.state, check)
+    }
+
+    fn api(&self) -> ::std::result::Result<(), String> {
+        match self.level.heart_beat() {
+            Ok(ref r) if r.ok => Ok(()),
+            Ok(r) => Err(r.error),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    fn venue(&self, venue: &str) -> ::std::result::Result<(), String> {
+        match self.level.venue_heart_beat(venue) {
+            Ok(ref r) if r.ok => Ok(()),
+            Ok(_) => Err(format!("{} isn't ok", venue)),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+impl<T: HttpClient + Clone + Send + 'static> Watchdog<T> {
+    /// Poll on a thread of its own until the handle is stopped or dropped.
+    pub fn spawn(mut self) -> WatchdogHandle {
+        let stop = Arc::new(AtomicBool::new(false));
+        let state = self.state.clone();
+        let stopping = stop.clone();
+        let thread = thread::spawn(move || {
+            while !stopping.load(Ordering::SeqCst) {
+                let started = Instant::now();
+                self.poll();
+                // Sleep in short steps so stopping doesn't wait out the
+                // whole interval.
+                while !stopping.load(Ordering::SeqCst) && started.elapsed() < self.interval {
+                    let left = self.interval.saturating_sub(started.elapsed());
+                    thread::sleep(left.min(Duration::from_millis(50)));
+                }
+            }
+        });
+        WatchdogHandle {
+            stop: stop,
+            state: state,
+            thread: Some(thread),
+        }
+    }
+}
+
+fn instance<T>(client: &Client<T>, id: i64) -> ::std::result::Result<(), String>
+    where T: HttpClient + Clone
+{
+    match client.instance_status(id) {
+        Ok(ref s) if s.ok && !s.done => Ok(()),
+        Ok(ref s) if s.done => Err(format!("level is done ({})", s.state)),
+        Ok(s) => Err(format!("instance isn't ok ({})", s.state)),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn is_up(state: &State, check: &Check) -> Option<bool> {
+    state.lock().unwrap_or_else(|e| e.into_inner()).get(check).cloned()
+}
+
+/// A running watchdog. Dropping it stops the thread.
+pub struct WatchdogHandle {
+    stop: Arc<AtomicBool>,
+    state: State,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl WatchdogHandle {
+    /// Whether a check was up last time it ran, or `None` before then.
+    pub fn is_up(&self, check: &Check) -> Option<bool> {
+        is_up(&self.state, check)
+    }
+
+    /// Whether every check has run and was up. Something to test before
+    /// sending an order.
+    pub fn all_up(&self) -> bool {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        !state.is_empty() && state.values().all(|&up| up)
+    }
+
+    /// The checks that are down.
+    pub fn down(&self) -> Vec<Check> {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.iter().filter(|&(_, &up)| !up).map(|(c, _)| c.clone()).collect()
+    }
+
+    /// Stop the thread and wait for it to finish.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(t) = self.thread.take() {
+            let _ = t.join();
+        }
+    }
+}
+
+impl Drop for WatchdogHandle {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use client::Client;
+    use data::{HeartBeatResponse, InstanceStatusResponse, Level, VenueHeartBeatResponse};
+    use endpoint::ApiProfile;
+    use error::{Error, Result};
+    use http::HttpClient;
+    use serde_json;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// A venue that can be taken down, and a level that can end.
+    #[derive(Debug, Clone, Default)]
+    struct TestSim {
+        venue_down: Arc<Mutex<bool>>,
+        done: Arc<Mutex<bool>>,
+    }
+
+    impl HttpClient for TestSim {
+        fn get(&self, url: &str) -> Result<String> {
+            if url.ends_with("/ob/api/heartbeat") {
+                return Ok(serde_json::to_string(&HeartBeatResponse {
+                        ok: true,
+                        error: String::new(),
+                    })
+                    .unwrap());
+            }
+            if url.ends_with("/venues/TESTEX/heartbeat") {
+                if *self.venue_down.lock().unwrap() {
+                    return Err(Error::Parse("no such venue".to_owned()));
+                }
+                return Ok(serde_json::to_string(&VenueHeartBeatResponse {
+                        ok: true,
+                        venue: "TESTEX".into(),
+                    })
+                    .unwrap());
+            }
+            assert!(url.ends_with("/gm/instances/7"), "unexpected get {}", url);
+            let done = *self.done.lock().unwrap();
+            Ok(serde_json::to_string(&InstanceStatusResponse {
+                    ok: true,
+                    done: done,
+                    id: 7,
+                    state: if done { "closed" } else { "open" }.to_owned(),
+                    details: None,
+                    flash: Some(HashMap::new()),
+                })
+                .unwrap())
+        }
+        fn post(&self, url: &str, _: Option<&str>) -> Result<String> {
+            panic!("unexpected post {}", url)
+        }
+        fn delete(&self, url: &str) -> Result<String> {
+            panic!("unexpected delete {}", url)
+        }
+    }
+
+    fn watchdog(sim: &TestSim) -> Watchdog<TestSim> {
+        let client = Client::with_http_client(sim.clone(),
+                                              "http://localhost:8000",
+                                              ApiProfile::default());
+        let level = Level {
+            instance_id: 7,
+            venues: vec!["TESTEX".into()],
+            ..Level::default()
+        };
+        Watchdog::new(client.level_client(level)).instance(client, 7)
+    }
+
+    #[test]
+    fn test_poll_reports_changes() {
+        let sim = TestSim::default();
+        let mut dog = watchdog(&sim);
+        let rx = dog.subscribe();
+        let first = dog.poll();
+        assert_eq!(vec![Check::Api, Check::Venue("TESTEX".into()), Check::Instance(7)],
+                   first.iter().map(|t| t.check.clone()).collect::<Vec<_>>());
+        assert!(first.iter().all(|t| t.up));
+        assert!(dog.poll().is_empty());
+
+        *sim.venue_down.lock().unwrap() = true;
+        *sim.done.lock().unwrap() = true;
+        let down = dog.poll();
+        assert_eq!(2, down.len());
+        assert_eq!(Check::Venue("TESTEX".into()), down[0].check);
+        assert!(!down[0].up);
+        assert_eq!(Some("level is done (closed)".to_owned()), down[1].reason);
+        assert_eq!(Some(false), dog.is_up(&Check::Instance(7)));
+        assert_eq!(5, rx.try_iter().count());
+    }
+
+    #[test]
+    fn test_spawn() {
+        let sim = TestSim::default();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let record = seen.clone();
+        let mut dog = watchdog(&sim)
+            .interval(Duration::from_millis(10))
+            .on_change(move |t| record.lock().unwrap().push(t.up));
+        let rx = dog.subscribe();
+        let handle = dog.spawn();
+        for _ in 0..3 {
+            assert!(rx.recv_timeout(Duration::from_secs(5)).unwrap().up);
+        }
+        assert!(handle.all_up());
+        *sim.done.lock().unwrap() = true;
+        let t = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!((Check::Instance(7), false), (t.check, t.up));
+        assert_eq!(vec![Check::Instance(7)], handle.down());
+        handle.stop();
+        assert_eq!(vec![true, true, true, false], *seen.lock().unwrap());
+    }
+}